@@ -0,0 +1,80 @@
+extern crate criterion;
+extern crate ton_connect;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ton_connect::crypto::{
+    decrypt_message_bytes, encrypt_message, generate_random_nonce, ClientKeypair, PeerChannel,
+};
+
+/// Sizes of typical bridge messages: a disconnect request, a transaction, a batch of messages.
+const SIZES: &[usize] = &[64, 512, 4096];
+
+fn encrypt(c: &mut Criterion) {
+    let app = ClientKeypair::generate_random();
+    let wallet = ClientKeypair::generate_random();
+    let wallet_public = wallet.get_hex_public();
+    let channel = PeerChannel::from_hex(&app, &wallet_public).unwrap();
+    let nonce = generate_random_nonce();
+
+    let mut group = c.benchmark_group("encrypt");
+    for &size in SIZES {
+        let plaintext = vec![0x42u8; size];
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(
+            BenchmarkId::new("encrypt_message", size),
+            &plaintext,
+            |b, plaintext| {
+                b.iter(|| {
+                    encrypt_message(app.secret(), black_box(plaintext), &nonce, &wallet_public)
+                        .unwrap()
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("PeerChannel", size),
+            &plaintext,
+            |b, plaintext| b.iter(|| channel.encrypt(black_box(plaintext), &nonce).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+fn decrypt(c: &mut Criterion) {
+    let app = ClientKeypair::generate_random();
+    let wallet = ClientKeypair::generate_random();
+    let wallet_public = wallet.get_hex_public();
+    let channel = PeerChannel::from_hex(&app, &wallet_public).unwrap();
+    let nonce = generate_random_nonce();
+
+    let mut group = c.benchmark_group("decrypt");
+    for &size in SIZES {
+        let ciphertext = PeerChannel::new(&wallet, app.public.clone())
+            .encrypt(&vec![0x42u8; size], &nonce)
+            .unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(
+            BenchmarkId::new("decrypt_message_bytes", size),
+            &ciphertext,
+            |b, ciphertext| {
+                b.iter(|| {
+                    decrypt_message_bytes(
+                        app.secret(),
+                        black_box(ciphertext),
+                        &nonce,
+                        &wallet_public,
+                    )
+                    .unwrap()
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("PeerChannel", size),
+            &ciphertext,
+            |b, ciphertext| b.iter(|| channel.decrypt(black_box(ciphertext), &nonce).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encrypt, decrypt);
+criterion_main!(benches);
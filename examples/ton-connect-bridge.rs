@@ -5,8 +5,7 @@ extern crate ton_connect;
 use eventsource::reqwest::Client;
 use reqwest::Url;
 use ton_connect::{
-    base64::{engine::general_purpose, Engine as _},
-    crypto::{decrypt_message, ClientKeypair, NONCE_LENGTH},
+    crypto::{open_from, ClientKeypair},
     helpers::{create_listen_url, create_universal_link},
     types::{BridgeMessage, ConnectItem, ConnectRequest, Topic, WalletEvent},
 };
@@ -42,13 +41,8 @@ fn main() {
         let event = event.unwrap();
         if event.id.is_some() {
             let bridge_msg: BridgeMessage = serde_json::from_str(&event.data).unwrap();
-            let msg = &general_purpose::STANDARD
-                .decode(bridge_msg.message)
-                .expect("invalid base64 message from bridge");
-            let (nonce, ciphertext) = msg.split_at(NONCE_LENGTH);
-            let plaintext =
-                decrypt_message(&client_a.secret, &ciphertext, &nonce, &bridge_msg.from).unwrap();
-            let wallet_event: WalletEvent = serde_json::from_str(&plaintext).unwrap();
+            let plaintext = open_from(&client_a, &bridge_msg).unwrap();
+            let wallet_event: WalletEvent = serde_json::from_slice(&plaintext).unwrap();
             println!("{}", serde_json::to_string_pretty(&wallet_event).unwrap());
         }
     }
@@ -0,0 +1,299 @@
+//! TON account addresses in raw (`<workchain>:<hex hash>`) and user-friendly forms.
+//!
+//! The user-friendly form is 36 bytes encoded with base64 or base64url: a tag byte with
+//! the bounceable and testnet flags, the workchain, the account hash and a CRC16 checksum.
+
+use crate::Error;
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryInto;
+use std::fmt;
+use std::str::FromStr;
+
+const BOUNCEABLE_TAG: u8 = 0x11;
+const NON_BOUNCEABLE_TAG: u8 = 0x51;
+const TESTNET_FLAG: u8 = 0x80;
+
+/// Address of a TON account.
+///
+/// The bounceable and testnet flags only exist in the user-friendly form, an address parsed
+/// from the raw form is bounceable and not for testnet. Compare `workchain` and `hash` when
+/// the flags do not matter.
+///
+/// Serializes to the raw form used by the TON Connect protocol, deserializes from any form.
+///
+/// # Example
+///
+/// ```
+/// use ton_connect::address::TonAddress;
+///
+/// let address: TonAddress = "EQDcab46mJsVE_FOnk7MRv9nU3cKKxmROHCP9mI4wnGemQ7Z".parse().unwrap();
+/// assert_eq!(address.workchain(), 0);
+/// assert!(address.is_bounceable());
+/// assert_eq!(
+///     address.to_raw(),
+///     "0:dc69be3a989b1513f14e9e4ecc46ff6753770a2b199138708ff66238c2719e99"
+/// );
+/// assert_eq!(
+///     address.with_bounceable(false).to_friendly(true).unwrap(),
+///     "UQDcab46mJsVE_FOnk7MRv9nU3cKKxmROHCP9mI4wnGemVMc"
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TonAddress {
+    workchain: i32,
+    hash: [u8; 32],
+    bounceable: bool,
+    testnet: bool,
+}
+
+impl TonAddress {
+    /// Creates a bounceable mainnet address.
+    pub fn new(workchain: i32, hash: [u8; 32]) -> Self {
+        Self {
+            workchain,
+            hash,
+            bounceable: true,
+            testnet: false,
+        }
+    }
+
+    /// Parses the raw form `<workchain>:<hex hash>`.
+    pub fn from_raw(address: &str) -> Result<Self, Error> {
+        let (workchain, hash) = address
+            .split_once(':')
+            .ok_or(Error::Address("invalid raw address"))?;
+        let workchain = workchain
+            .parse()
+            .map_err(|_| Error::Address("invalid workchain"))?;
+        let hash: [u8; 32] = hex::decode(hash)?
+            .try_into()
+            .map_err(|_| Error::Address("invalid address hash length"))?;
+        Ok(Self::new(workchain, hash))
+    }
+
+    /// Parses the user-friendly form, encoded with either base64 or base64url.
+    pub fn from_friendly(address: &str) -> Result<Self, Error> {
+        if address.len() != 48 {
+            return Err(Error::Address("invalid user-friendly address length"));
+        }
+        let bytes = if address.contains(['-', '_']) {
+            general_purpose::URL_SAFE.decode(address)?
+        } else {
+            general_purpose::STANDARD.decode(address)?
+        };
+        let bytes: [u8; 36] = bytes
+            .try_into()
+            .map_err(|_| Error::Address("invalid user-friendly address length"))?;
+
+        let checksum = u16::from_be_bytes([bytes[34], bytes[35]]);
+        if checksum != crc16(&bytes[..34]) {
+            return Err(Error::Address("invalid user-friendly address checksum"));
+        }
+        let testnet = bytes[0] & TESTNET_FLAG != 0;
+        let bounceable = match bytes[0] & !TESTNET_FLAG {
+            BOUNCEABLE_TAG => true,
+            NON_BOUNCEABLE_TAG => false,
+            _ => return Err(Error::Address("invalid user-friendly address tag")),
+        };
+        Ok(Self {
+            workchain: bytes[1] as i8 as i32,
+            hash: bytes[2..34].try_into().expect("32 bytes hash"),
+            bounceable,
+            testnet,
+        })
+    }
+
+    pub fn workchain(&self) -> i32 {
+        self.workchain
+    }
+
+    /// Account hash, the hash of the stateinit the contract was deployed with.
+    pub fn hash(&self) -> &[u8; 32] {
+        &self.hash
+    }
+
+    pub fn is_bounceable(&self) -> bool {
+        self.bounceable
+    }
+
+    pub fn is_testnet(&self) -> bool {
+        self.testnet
+    }
+
+    pub fn with_bounceable(self, bounceable: bool) -> Self {
+        Self { bounceable, ..self }
+    }
+
+    pub fn with_testnet(self, testnet: bool) -> Self {
+        Self { testnet, ..self }
+    }
+
+    /// Formats the raw form `<workchain>:<hex hash>`.
+    pub fn to_raw(&self) -> String {
+        format!("{}:{}", self.workchain, hex::encode(self.hash))
+    }
+
+    /// Formats the user-friendly form, with base64url if `url_safe`, as wallets show it.
+    ///
+    /// The workchain is stored in a single byte, other workchains than -128 to 127 are an error.
+    pub fn to_friendly(&self, url_safe: bool) -> Result<String, Error> {
+        let workchain: i8 = self
+            .workchain
+            .try_into()
+            .map_err(|_| Error::Address("workchain does not fit the user-friendly form"))?;
+        let mut bytes = Vec::with_capacity(36);
+        let tag = if self.bounceable {
+            BOUNCEABLE_TAG
+        } else {
+            NON_BOUNCEABLE_TAG
+        };
+        bytes.push(if self.testnet {
+            tag | TESTNET_FLAG
+        } else {
+            tag
+        });
+        bytes.push(workchain as u8);
+        bytes.extend(self.hash);
+        let checksum = crc16(&bytes);
+        bytes.extend(checksum.to_be_bytes());
+        Ok(if url_safe {
+            general_purpose::URL_SAFE.encode(bytes)
+        } else {
+            general_purpose::STANDARD.encode(bytes)
+        })
+    }
+}
+
+impl FromStr for TonAddress {
+    type Err = Error;
+
+    /// Parses the raw or the user-friendly form.
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        if address.contains(':') {
+            Self::from_raw(address)
+        } else {
+            Self::from_friendly(address)
+        }
+    }
+}
+
+/// Formats the raw form.
+impl fmt::Display for TonAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_raw())
+    }
+}
+
+impl Serialize for TonAddress {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_raw())
+    }
+}
+
+impl<'de> Deserialize<'de> for TonAddress {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let address = String::deserialize(deserializer)?;
+        address.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// CRC16/XMODEM checksum of the user-friendly form.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW: &str = "0:dc69be3a989b1513f14e9e4ecc46ff6753770a2b199138708ff66238c2719e99";
+
+    #[test]
+    fn test_parse_forms() {
+        let raw = TonAddress::from_raw(RAW).unwrap();
+        let url_safe: TonAddress = "EQDcab46mJsVE_FOnk7MRv9nU3cKKxmROHCP9mI4wnGemQ7Z"
+            .parse()
+            .unwrap();
+        let standard: TonAddress = "EQDcab46mJsVE/FOnk7MRv9nU3cKKxmROHCP9mI4wnGemQ7Z"
+            .parse()
+            .unwrap();
+        assert_eq!(raw, url_safe);
+        assert_eq!(raw, standard);
+
+        let testnet: TonAddress = "0QDcab46mJsVE_FOnk7MRv9nU3cKKxmROHCP9mI4wnGemeiW"
+            .parse()
+            .unwrap();
+        assert!(testnet.is_testnet());
+        assert!(!testnet.is_bounceable());
+        assert_eq!(testnet.hash(), raw.hash());
+        assert_eq!(testnet.to_raw(), RAW);
+    }
+
+    #[test]
+    fn test_format_friendly() {
+        let address = TonAddress::from_raw(RAW).unwrap();
+        assert_eq!(
+            address.with_testnet(true).to_friendly(true).unwrap(),
+            "kQDcab46mJsVE_FOnk7MRv9nU3cKKxmROHCP9mI4wnGembVT"
+        );
+        assert_eq!(
+            TonAddress::new(0, [0; 32]).to_friendly(true).unwrap(),
+            "EQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAM9c"
+        );
+
+        let masterchain = TonAddress::from_raw(
+            "-1:412410771DA82CBA306A55FA9E0D43C9D245E38133CB58F1457DFB8D5CD8892F",
+        )
+        .unwrap();
+        let friendly = masterchain.to_friendly(false).unwrap();
+        assert_eq!(friendly, "Ef9BJBB3HagsujBqVfqeDUPJ0kXjgTPLWPFFffuNXNiJL7nI");
+        assert_eq!(TonAddress::from_friendly(&friendly).unwrap(), masterchain);
+
+        assert!(matches!(
+            TonAddress::new(128, [0; 32]).to_friendly(true),
+            Err(Error::Address(_))
+        ));
+        assert!(TonAddress::from_raw(&RAW.replacen('0', "-129", 1))
+            .unwrap()
+            .to_friendly(true)
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!("EQDcab46mJsVE_FOnk7MRv9nU3cKKxmROHCP9mI4wnGemQ7a"
+            .parse::<TonAddress>()
+            .is_err());
+        assert!("0:dc69".parse::<TonAddress>().is_err());
+        assert!("x:".parse::<TonAddress>().is_err());
+        assert!("EQDcab46".parse::<TonAddress>().is_err());
+    }
+
+    #[test]
+    fn test_serde() {
+        let address: TonAddress =
+            serde_json::from_str("\"UQDcab46mJsVE_FOnk7MRv9nU3cKKxmROHCP9mI4wnGemVMc\"").unwrap();
+        assert_eq!(
+            serde_json::to_string(&address).unwrap(),
+            format!("\"{}\"", RAW)
+        );
+    }
+}
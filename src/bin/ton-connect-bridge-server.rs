@@ -0,0 +1,25 @@
+extern crate tokio;
+extern crate ton_connect;
+
+use std::env;
+use std::net::TcpListener;
+use ton_connect::bridge_server::{BridgeServer, BridgeServerConfig};
+
+/// Runs a local bridge: `ton-connect-bridge-server [address]`, on 127.0.0.1:8081 by default.
+#[tokio::main]
+async fn main() {
+    let address = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:8081".to_string());
+    let listener = TcpListener::bind(&address).expect("cannot bind the address");
+    let config = BridgeServerConfig::default();
+    println!(
+        "Bridge url: http://{}{}",
+        listener.local_addr().expect("bound address"),
+        config.path
+    );
+    if let Err(err) = BridgeServer::new(config).run(listener).await {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
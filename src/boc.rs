@@ -0,0 +1,736 @@
+//! TON cells and their bag-of-cells (BOC) serialization.
+
+use crate::Error;
+use base64::{engine::general_purpose, Engine as _};
+use sha2::{Digest, Sha256};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::convert::TryInto;
+use std::sync::Arc;
+
+/// Magic prefix of a serialized bag of cells.
+pub const BOC_MAGIC: [u8; 4] = [0xb5, 0xee, 0x9c, 0x72];
+/// Maximum number of data bits in a cell.
+pub const MAX_CELL_BITS: usize = 1023;
+/// Maximum number of references in a cell.
+pub const MAX_CELL_REFS: usize = 4;
+/// Maximum depth of a cell, as limited by the TVM.
+pub const MAX_CELL_DEPTH: u16 = 1024;
+
+/// A cell: up to 1023 bits of data and up to 4 references to other cells.
+///
+/// Only ordinary cells and library cells are supported, they both have level 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+    data: Vec<u8>,
+    bit_len: usize,
+    refs: Vec<Arc<Cell>>,
+    exotic: bool,
+    hash: [u8; 32],
+    depth: u16,
+}
+
+impl Cell {
+    /// Creates an ordinary cell from `bit_len` bits of `data` and the referenced cells.
+    pub fn new(data: Vec<u8>, bit_len: usize, refs: Vec<Arc<Cell>>) -> Result<Self, Error> {
+        Self::with_type(data, bit_len, refs, false)
+    }
+
+    fn with_type(
+        mut data: Vec<u8>,
+        bit_len: usize,
+        refs: Vec<Arc<Cell>>,
+        exotic: bool,
+    ) -> Result<Self, Error> {
+        if bit_len > MAX_CELL_BITS || data.len() * 8 < bit_len {
+            return Err(Error::Boc("invalid cell data length"));
+        }
+        if refs.len() > MAX_CELL_REFS {
+            return Err(Error::Boc("too many cell references"));
+        }
+        if exotic && (bit_len != 8 + 256 || data[0] != 2 || !refs.is_empty()) {
+            return Err(Error::Boc(
+                "unsupported exotic cell, only library cells are supported",
+            ));
+        }
+
+        // Keep exactly the bytes covering the data, with the unused bits zeroed.
+        data.truncate(bit_len.div_ceil(8));
+        if !bit_len.is_multiple_of(8) {
+            let last = data.len() - 1;
+            data[last] &= 0xff << (8 - bit_len % 8);
+        }
+
+        let depth = match refs.iter().map(|cell| cell.depth).max() {
+            Some(depth) => depth
+                .checked_add(1)
+                .filter(|depth| *depth <= MAX_CELL_DEPTH)
+                .ok_or(Error::Boc("cell depth is over 1024"))?,
+            None => 0,
+        };
+        let mut cell = Self {
+            data,
+            bit_len,
+            refs,
+            exotic,
+            hash: [0; 32],
+            depth,
+        };
+        cell.hash = cell.representation_hash();
+        Ok(cell)
+    }
+
+    /// Data bits, packed from the most significant bit of the first byte.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn bit_len(&self) -> usize {
+        self.bit_len
+    }
+
+    pub fn refs(&self) -> &[Arc<Cell>] {
+        &self.refs
+    }
+
+    pub fn is_exotic(&self) -> bool {
+        self.exotic
+    }
+
+    /// Representation hash of the cell, which identifies it and, for a stateinit, the contract address.
+    pub fn hash(&self) -> [u8; 32] {
+        self.hash
+    }
+
+    /// Longest path of references from this cell.
+    pub fn depth(&self) -> u16 {
+        self.depth
+    }
+
+    /// Returns a reader over the data bits and references of the cell.
+    pub fn parser(&self) -> CellParser<'_> {
+        CellParser {
+            cell: self,
+            bit_offset: 0,
+            ref_offset: 0,
+        }
+    }
+
+    /// Descriptor bytes of the cell: references count and exotic flag, then the data length in half-bytes.
+    fn descriptors(&self) -> [u8; 2] {
+        let d1 = self.refs.len() as u8 + if self.exotic { 8 } else { 0 };
+        let d2 = (self.bit_len.div_ceil(8) + self.bit_len / 8) as u8;
+        [d1, d2]
+    }
+
+    /// Data bytes with the completion tag appended when the bits do not fill the last byte.
+    fn padded_data(&self) -> Vec<u8> {
+        let mut data = self.data.clone();
+        if !self.bit_len.is_multiple_of(8) {
+            let last = data.len() - 1;
+            data[last] |= 0x80 >> (self.bit_len % 8);
+        }
+        data
+    }
+
+    fn representation_hash(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.descriptors());
+        hasher.update(self.padded_data());
+        for cell in &self.refs {
+            hasher.update(cell.depth.to_be_bytes());
+        }
+        for cell in &self.refs {
+            hasher.update(cell.hash);
+        }
+        hasher.finalize().into()
+    }
+}
+
+/// Sequential reader over the data bits and references of a cell.
+pub struct CellParser<'a> {
+    cell: &'a Cell,
+    bit_offset: usize,
+    ref_offset: usize,
+}
+
+impl<'a> CellParser<'a> {
+    /// Number of data bits not read yet.
+    pub fn remaining_bits(&self) -> usize {
+        self.cell.bit_len - self.bit_offset
+    }
+
+    pub fn load_bit(&mut self) -> Result<bool, Error> {
+        if self.remaining_bits() == 0 {
+            return Err(Error::Boc("cell data underflow"));
+        }
+        let byte = self.cell.data[self.bit_offset / 8];
+        let bit = byte & (0x80 >> (self.bit_offset % 8)) != 0;
+        self.bit_offset += 1;
+        Ok(bit)
+    }
+
+    /// Reads an unsigned big-endian integer of `bits` bits, at most 64.
+    pub fn load_uint(&mut self, bits: usize) -> Result<u64, Error> {
+        if bits > 64 {
+            return Err(Error::Boc("integer is too wide"));
+        }
+        if self.remaining_bits() < bits {
+            return Err(Error::Boc("cell data underflow"));
+        }
+        let mut value = 0u64;
+        for _ in 0..bits {
+            value = (value << 1) | self.load_bit()? as u64;
+        }
+        Ok(value)
+    }
+
+    /// Reads `len` whole bytes, not necessarily byte aligned.
+    pub fn load_bytes(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+        if self.remaining_bits() < len * 8 {
+            return Err(Error::Boc("cell data underflow"));
+        }
+        (0..len)
+            .map(|_| self.load_uint(8).map(|byte| byte as u8))
+            .collect()
+    }
+
+    /// Reads the next reference.
+    pub fn load_ref(&mut self) -> Result<&'a Arc<Cell>, Error> {
+        let cell = self
+            .cell
+            .refs
+            .get(self.ref_offset)
+            .ok_or(Error::Boc("cell references underflow"))?;
+        self.ref_offset += 1;
+        Ok(cell)
+    }
+
+    /// Reads a `Maybe ^Cell`: a presence bit, then a reference if it is set.
+    pub fn load_maybe_ref(&mut self) -> Result<Option<&'a Arc<Cell>>, Error> {
+        if self.load_bit()? {
+            self.load_ref().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Writer for the data bits and references of a new cell.
+///
+/// # Example
+///
+/// ```
+/// use ton_connect::boc::CellBuilder;
+///
+/// let mut builder = CellBuilder::new();
+/// builder.store_uint(0, 32).unwrap().store_bytes(b"Hello").unwrap();
+/// let cell = builder.build().unwrap();
+/// assert_eq!(cell.bit_len(), 72);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CellBuilder {
+    data: Vec<u8>,
+    bit_len: usize,
+    refs: Vec<Arc<Cell>>,
+}
+
+impl CellBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn store_bit(&mut self, bit: bool) -> Result<&mut Self, Error> {
+        if self.bit_len == MAX_CELL_BITS {
+            return Err(Error::Boc("cell data overflow"));
+        }
+        if self.bit_len.is_multiple_of(8) {
+            self.data.push(0);
+        }
+        if bit {
+            let last = self.data.len() - 1;
+            self.data[last] |= 0x80 >> (self.bit_len % 8);
+        }
+        self.bit_len += 1;
+        Ok(self)
+    }
+
+    /// Writes `value` as an unsigned big-endian integer of `bits` bits, at most 64.
+    pub fn store_uint(&mut self, value: u64, bits: usize) -> Result<&mut Self, Error> {
+        if bits > 64 || (bits < 64 && value >> bits != 0) {
+            return Err(Error::Boc("integer does not fit the bits"));
+        }
+        if self.bit_len + bits > MAX_CELL_BITS {
+            return Err(Error::Boc("cell data overflow"));
+        }
+        for offset in (0..bits).rev() {
+            self.store_bit(value >> offset & 1 == 1)?;
+        }
+        Ok(self)
+    }
+
+    /// Writes whole bytes, not necessarily byte aligned.
+    pub fn store_bytes(&mut self, bytes: &[u8]) -> Result<&mut Self, Error> {
+        if self.bit_len + bytes.len() * 8 > MAX_CELL_BITS {
+            return Err(Error::Boc("cell data overflow"));
+        }
+        for byte in bytes {
+            self.store_uint(*byte as u64, 8)?;
+        }
+        Ok(self)
+    }
+
+    pub fn store_ref(&mut self, cell: Arc<Cell>) -> Result<&mut Self, Error> {
+        if self.refs.len() == MAX_CELL_REFS {
+            return Err(Error::Boc("too many cell references"));
+        }
+        self.refs.push(cell);
+        Ok(self)
+    }
+
+    /// Writes a `Maybe ^Cell`: a presence bit, then a reference if there is a cell.
+    pub fn store_maybe_ref(&mut self, cell: Option<Arc<Cell>>) -> Result<&mut Self, Error> {
+        match cell {
+            Some(cell) => self.store_bit(true)?.store_ref(cell),
+            None => self.store_bit(false),
+        }
+    }
+
+    /// Number of data bits written so far.
+    pub fn bit_len(&self) -> usize {
+        self.bit_len
+    }
+
+    pub fn build(&self) -> Result<Cell, Error> {
+        Cell::new(self.data.clone(), self.bit_len, self.refs.clone())
+    }
+}
+
+/// Deserializes a bag of cells and returns its root cells.
+pub fn deserialize(boc: &[u8]) -> Result<Vec<Arc<Cell>>, Error> {
+    let mut reader = ByteReader {
+        data: boc,
+        offset: 0,
+    };
+    if reader.read(4)? != BOC_MAGIC {
+        return Err(Error::Boc("invalid BOC magic"));
+    }
+    let flags = reader.read(1)?[0];
+    let has_idx = flags & 0x80 != 0;
+    let has_crc32c = flags & 0x40 != 0;
+    let size = (flags & 0x07) as usize;
+    if size == 0 || size > 4 {
+        return Err(Error::Boc("invalid BOC reference size"));
+    }
+    let off_bytes = reader.read(1)?[0] as usize;
+    if off_bytes == 0 || off_bytes > 8 {
+        return Err(Error::Boc("invalid BOC offset size"));
+    }
+    let cells_count = reader.read_uint(size)? as usize;
+    let roots_count = reader.read_uint(size)? as usize;
+    let absent_count = reader.read_uint(size)?;
+    if roots_count == 0 || absent_count != 0 || roots_count > cells_count {
+        return Err(Error::Boc("invalid BOC cell counts"));
+    }
+    // Every cell takes at least 2 bytes, so larger counts cannot be allocated for.
+    if cells_count > boc.len() - reader.offset {
+        return Err(Error::Boc("unexpected end of BOC"));
+    }
+    let _total_cells_size = reader.read_uint(off_bytes)?;
+    let roots = (0..roots_count)
+        .map(|_| reader.read_uint(size).map(|index| index as usize))
+        .collect::<Result<Vec<_>, _>>()?;
+    if has_idx {
+        let index_len = cells_count
+            .checked_mul(off_bytes)
+            .ok_or(Error::Boc("unexpected end of BOC"))?;
+        reader.read(index_len)?;
+    }
+
+    let mut raw_cells = Vec::with_capacity(cells_count);
+    for _ in 0..cells_count {
+        let d1 = reader.read(1)?[0];
+        let d2 = reader.read(1)?[0] as usize;
+        let refs_count = (d1 & 0x07) as usize;
+        let exotic = d1 & 0x08 != 0;
+        if d1 & 0x10 != 0 || d1 >> 5 != 0 {
+            return Err(Error::Boc(
+                "cells with stored hashes or levels are not supported",
+            ));
+        }
+        let data = reader.read(d2.div_ceil(2))?.to_vec();
+        let bit_len = if d2.is_multiple_of(2) {
+            d2 / 2 * 8
+        } else {
+            data_bit_len(&data)?
+        };
+        let refs = (0..refs_count)
+            .map(|_| reader.read_uint(size).map(|index| index as usize))
+            .collect::<Result<Vec<_>, _>>()?;
+        raw_cells.push((data, bit_len, refs, exotic));
+    }
+    if has_crc32c {
+        let checked = reader.offset;
+        let crc = u32::from_le_bytes(
+            reader
+                .read(4)?
+                .try_into()
+                .map_err(|_| Error::Boc("unexpected end of BOC"))?,
+        );
+        if crc != crc32c(&boc[..checked]) {
+            return Err(Error::Boc("invalid BOC checksum"));
+        }
+    }
+
+    // References always point forward, so the cells are built from the last one.
+    let mut cells: Vec<Option<Arc<Cell>>> = vec![None; cells_count];
+    for (index, (data, bit_len, refs, exotic)) in raw_cells.into_iter().enumerate().rev() {
+        let refs = refs
+            .into_iter()
+            .map(|ref_index| {
+                if ref_index <= index {
+                    return Err(Error::Boc("invalid BOC cell reference"));
+                }
+                cells
+                    .get(ref_index)
+                    .cloned()
+                    .flatten()
+                    .ok_or(Error::Boc("invalid BOC cell reference"))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        cells[index] = Some(Arc::new(Cell::with_type(data, bit_len, refs, exotic)?));
+    }
+
+    roots
+        .into_iter()
+        .map(|index| {
+            cells
+                .get(index)
+                .cloned()
+                .flatten()
+                .ok_or(Error::Boc("invalid BOC root index"))
+        })
+        .collect()
+}
+
+/// Deserializes a base64 (not url safe) encoded bag of cells with a single root.
+pub fn from_base64(boc: &str) -> Result<Arc<Cell>, Error> {
+    let boc = general_purpose::STANDARD.decode(boc)?;
+    let mut roots = deserialize(&boc)?;
+    if roots.len() != 1 {
+        return Err(Error::Boc("expected a single root cell"));
+    }
+    Ok(roots.remove(0))
+}
+
+/// Layout options of a serialized bag of cells.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SerializeOptions {
+    /// Write the offsets of the cells, to read them without parsing the whole BOC.
+    pub has_idx: bool,
+    /// Append a CRC32C checksum of the BOC.
+    pub has_crc32c: bool,
+}
+
+impl Default for SerializeOptions {
+    /// Checksum without index, the layout wallets produce.
+    fn default() -> Self {
+        Self {
+            has_idx: false,
+            has_crc32c: true,
+        }
+    }
+}
+
+/// Serializes the cells reachable from `roots` into a bag of cells.
+pub fn serialize(roots: &[Arc<Cell>], options: SerializeOptions) -> Result<Vec<u8>, Error> {
+    if roots.is_empty() {
+        return Err(Error::Boc("BOC needs at least one root cell"));
+    }
+    let cells = order_cells(roots);
+    let indexes: HashMap<[u8; 32], usize> = cells
+        .iter()
+        .enumerate()
+        .map(|(index, cell)| (cell.hash, index))
+        .collect();
+    let size = bytes_len(cells.len() as u64);
+
+    let mut cells_data = Vec::new();
+    let mut offsets = Vec::with_capacity(cells.len());
+    for cell in &cells {
+        cells_data.extend(cell.descriptors());
+        cells_data.extend(cell.padded_data());
+        for child in &cell.refs {
+            write_uint(&mut cells_data, indexes[&child.hash] as u64, size);
+        }
+        offsets.push(cells_data.len() as u64);
+    }
+    let off_bytes = bytes_len(cells_data.len() as u64);
+
+    let mut boc = BOC_MAGIC.to_vec();
+    let mut flags = size as u8;
+    if options.has_idx {
+        flags |= 0x80;
+    }
+    if options.has_crc32c {
+        flags |= 0x40;
+    }
+    boc.push(flags);
+    boc.push(off_bytes as u8);
+    write_uint(&mut boc, cells.len() as u64, size);
+    write_uint(&mut boc, roots.len() as u64, size);
+    write_uint(&mut boc, 0, size);
+    write_uint(&mut boc, cells_data.len() as u64, off_bytes);
+    for root in roots {
+        write_uint(&mut boc, indexes[&root.hash] as u64, size);
+    }
+    if options.has_idx {
+        for offset in offsets {
+            write_uint(&mut boc, offset, off_bytes);
+        }
+    }
+    boc.extend(cells_data);
+    if options.has_crc32c {
+        let crc = crc32c(&boc);
+        boc.extend(crc.to_le_bytes());
+    }
+    Ok(boc)
+}
+
+/// Serializes a single root cell into a base64 (not url safe) encoded bag of cells with a checksum.
+pub fn to_base64(root: &Arc<Cell>) -> String {
+    let boc = serialize(std::slice::from_ref(root), SerializeOptions::default())
+        .expect("BOC with a single root");
+    general_purpose::STANDARD.encode(boc)
+}
+
+/// Lists the distinct cells so that every cell comes before the cells it references.
+///
+/// Cells are taken in depth-first order whenever possible, which is the order other
+/// implementations use, so trees serialize to the same bytes.
+fn order_cells(roots: &[Arc<Cell>]) -> Vec<Arc<Cell>> {
+    // Distinct cells in depth-first order.
+    let mut visited: Vec<Arc<Cell>> = Vec::new();
+    let mut positions: HashMap<[u8; 32], usize> = HashMap::new();
+    let mut stack: Vec<&Arc<Cell>> = roots.iter().rev().collect();
+    while let Some(cell) = stack.pop() {
+        if positions.contains_key(&cell.hash) {
+            continue;
+        }
+        positions.insert(cell.hash, visited.len());
+        visited.push(cell.clone());
+        stack.extend(cell.refs.iter().rev());
+    }
+
+    let mut parents = vec![0usize; visited.len()];
+    for cell in &visited {
+        for child in &cell.refs {
+            parents[positions[&child.hash]] += 1;
+        }
+    }
+    // Take the earliest cell in depth-first order among those whose parents are all placed.
+    let mut ready: BinaryHeap<Reverse<usize>> = (0..visited.len())
+        .filter(|position| parents[*position] == 0)
+        .map(Reverse)
+        .collect();
+    let mut ordered = Vec::with_capacity(visited.len());
+    while let Some(Reverse(position)) = ready.pop() {
+        let cell = &visited[position];
+        for child in &cell.refs {
+            let child = positions[&child.hash];
+            parents[child] -= 1;
+            if parents[child] == 0 {
+                ready.push(Reverse(child));
+            }
+        }
+        ordered.push(cell.clone());
+    }
+    ordered
+}
+
+/// CRC32C (Castagnoli) checksum, as stored at the end of a BOC.
+fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0x82f6_3b78
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Number of bytes needed to write `value`, at least one.
+fn bytes_len(value: u64) -> usize {
+    (64 - value.leading_zeros() as usize).div_ceil(8).max(1)
+}
+
+fn write_uint(buf: &mut Vec<u8>, value: u64, len: usize) {
+    buf.extend(&value.to_be_bytes()[8 - len..]);
+}
+
+/// Counts the data bits of a cell whose last byte holds a completion tag.
+fn data_bit_len(data: &[u8]) -> Result<usize, Error> {
+    let last = *data.last().ok_or(Error::Boc("invalid cell data"))?;
+    if last == 0 {
+        return Err(Error::Boc("missing cell completion tag"));
+    }
+    Ok(data.len() * 8 - last.trailing_zeros() as usize - 1)
+}
+
+struct ByteReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn read(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(Error::Boc("unexpected end of BOC"))?;
+        let bytes = &self.data[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn read_uint(&mut self, len: usize) -> Result<u64, Error> {
+        let mut bytes = [0u8; 8];
+        bytes[8 - len..].copy_from_slice(self.read(len)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WALLET_STATE_INIT: &str = "te6cckECFgEAAwQAAgE0ARUBFP8A9KQT9LzyyAsCAgEgAxACAUgEBwLm0AHQ0wMhcbCSXwTgItdJwSCSXwTgAtMfIYIQcGx1Z70ighBkc3RyvbCSXwXgA/pAMCD6RAHIygfL/8nQ7UTQgQFA1yH0BDBcgQEI9ApvoTGzkl8H4AXTP8glghBwbHVnupI4MOMNA4IQZHN0crqSXwbjDQUGAHgB+gD0BDD4J28iMFAKoSG+8uBQghBwbHVngx6xcIAYUATLBSbPFlj6Ahn0AMtpF8sfUmDLPyDJgED7AAYAilAEgQEI9Fkw7UTQgQFA1yDIAc8W9ADJ7VQBcrCOI4IQZHN0coMesXCAGFAFywVQA88WI/oCE8tqyx/LP8mAQPsAkl8D4gIBIAgPAgEgCQ4CAVgKCwA9sp37UTQgQFA1yH0BDACyMoHy//J0AGBAQj0Cm+hMYAIBIAwNABmtznaiaEAga5Drhf/AABmvHfaiaEAQa5DrhY/AABG4yX7UTQ1wsfgAWb0kK29qJoQICga5D6AhhHDUCAhHpJN9KZEM5pA+n/mDeBKAG3gQFImHFZ8xhAT48oMI1xgg0x/TH9MfAvgju/Jk7UTQ0x/TH9P/9ATRUUO68qFRUbryogX5AVQQZPkQ8qP4ACSkyMsfUkDLH1Iwy/9SEPQAye1U+A8B0wchwACfbFGTINdKltMH1AL7AOgw4CHAAeMAIcAC4wABwAORMOMNA6TIyx8Syx/L/xESExQAbtIH+gDU1CL5AAXIygcVy//J0Hd0gBjIywXLAiLPFlAF+gIUy2sSzMzJc/sAyEAUgQEI9FHypwIAcIEBCNcY+gDTP8hUIEeBAQj0UfKnghBub3RlcHSAGMjLBcsCUAbPFlAE+gIUy2oSyx/LP8lz+wACAGyBAQjXGPoA0z8wUiSBAQj0WfKnghBkc3RycHSAGMjLBcsCUAXPFlAD+gITy2rLHxLLP8lz+wAACvQAye1UAFEAAAAAKamjFzIbcXo6RVCWtsPSCtP2YgcSVVfay7iI/KngACOqib//QMgBKq8=";
+
+    #[test]
+    fn test_deserialize_wallet_state_init() {
+        let state_init = from_base64(WALLET_STATE_INIT).unwrap();
+        // The address of a contract is the hash of its stateinit.
+        assert_eq!(
+            hex::encode(state_init.hash()),
+            "dc69be3a989b1513f14e9e4ecc46ff6753770a2b199138708ff66238c2719e99"
+        );
+        assert_eq!(state_init.refs().len(), 2);
+        assert_eq!(state_init.bit_len(), 5);
+    }
+
+    #[test]
+    fn test_serialize_wallet_state_init() {
+        let state_init = from_base64(WALLET_STATE_INIT).unwrap();
+        assert_eq!(to_base64(&state_init), WALLET_STATE_INIT);
+
+        let options = SerializeOptions {
+            has_idx: true,
+            has_crc32c: false,
+        };
+        let boc = serialize(std::slice::from_ref(&state_init), options).unwrap();
+        assert_eq!(boc[4], 0x81);
+        assert_eq!(deserialize(&boc).unwrap(), vec![state_init]);
+    }
+
+    #[test]
+    fn test_deserialize_invalid_checksum() {
+        let mut boc = general_purpose::STANDARD.decode(WALLET_STATE_INIT).unwrap();
+        let last = boc.len() - 1;
+        boc[last] ^= 1;
+        assert!(deserialize(&boc).is_err());
+    }
+
+    #[test]
+    fn test_serialize_shared_cells() {
+        let leaf = Arc::new(Cell::new(vec![0xab], 8, vec![]).unwrap());
+        let mut builder = CellBuilder::new();
+        builder.store_ref(leaf.clone()).unwrap();
+        let middle = Arc::new(builder.build().unwrap());
+        let mut builder = CellBuilder::new();
+        builder.store_ref(leaf).unwrap().store_ref(middle).unwrap();
+        let root = Arc::new(builder.build().unwrap());
+
+        let boc = serialize(std::slice::from_ref(&root), SerializeOptions::default()).unwrap();
+        // The leaf is stored once, after both cells referencing it.
+        assert_eq!(boc[6], 3);
+        assert_eq!(deserialize(&boc).unwrap(), vec![root]);
+    }
+
+    #[test]
+    fn test_cell_builder() {
+        let mut builder = CellBuilder::new();
+        builder
+            .store_bit(true)
+            .unwrap()
+            .store_uint(0b011, 3)
+            .unwrap()
+            .store_bytes(&[0b0110_1111])
+            .unwrap()
+            .store_uint(0b1111101, 7)
+            .unwrap()
+            .store_maybe_ref(None)
+            .unwrap();
+        let cell = builder.build().unwrap();
+        assert_eq!(
+            cell,
+            Cell::new(vec![0b1011_0110, 0xff, 0b1010_0000], 20, vec![]).unwrap()
+        );
+
+        assert!(CellBuilder::new().store_uint(4, 2).is_err());
+        let mut builder = CellBuilder::new();
+        builder
+            .store_bytes(&[0; 127])
+            .unwrap()
+            .store_uint(0, 7)
+            .unwrap();
+        assert!(builder.store_bit(false).is_err());
+    }
+
+    #[test]
+    fn test_cell_parser() {
+        let cell = Cell::new(vec![0b1011_0110, 0xff, 0b1010_0000], 19, vec![]).unwrap();
+        let mut parser = cell.parser();
+        assert!(parser.load_bit().unwrap());
+        assert_eq!(parser.load_uint(3).unwrap(), 0b011);
+        assert_eq!(parser.load_bytes(1).unwrap(), vec![0b0110_1111]);
+        assert_eq!(parser.remaining_bits(), 7);
+        assert!(parser.load_uint(8).is_err());
+        assert!(parser.load_ref().is_err());
+    }
+
+    #[test]
+    fn test_deserialize_invalid() {
+        assert!(deserialize(&[]).is_err());
+        assert!(deserialize(&[0xb5, 0xee, 0x9c, 0x72, 0x01]).is_err());
+        assert!(from_base64("te6cckECFgEAAwQAAgE0ARUBFP8A").is_err());
+    }
+
+    #[test]
+    fn test_deserialize_truncated_header() {
+        // 4 bytes references claiming 0xffffffff cells in a 23 bytes BOC.
+        let boc = [
+            0xb5, 0xee, 0x9c, 0x72, 0x04, 0x01, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01,
+            0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert!(matches!(
+            deserialize(&boc),
+            Err(Error::Boc("unexpected end of BOC"))
+        ));
+    }
+
+    #[test]
+    fn test_cell_depth_limit() {
+        let mut cell = Arc::new(Cell::new(vec![], 0, vec![]).unwrap());
+        for _ in 0..MAX_CELL_DEPTH {
+            cell = Arc::new(Cell::new(vec![], 0, vec![cell]).unwrap());
+        }
+        assert_eq!(cell.depth(), MAX_CELL_DEPTH);
+        assert!(Cell::new(vec![], 0, vec![cell]).is_err());
+    }
+}
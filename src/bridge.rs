@@ -0,0 +1,265 @@
+//! Clients for the TON Connect HTTP bridge.
+//!
+//! The bridge relays end-to-end encrypted messages between the app and the wallet.
+//! Messages are received from the `/events` server-sent events endpoint and sent
+//! with `POST /message`.
+
+use self::sse::SseDecoder;
+use crate::crypto::{open_from, ClientKeypair};
+use crate::helpers::create_listen_url;
+use crate::types::{BridgeMessage, RawWalletResponse, Topic, WalletEvent};
+use crate::Error;
+use std::time::{Duration, Instant};
+use url::Url;
+
+#[cfg(feature = "async")]
+mod async_client;
+#[cfg(feature = "blocking")]
+mod blocking;
+mod sse;
+#[cfg(test)]
+pub(crate) mod test_bridge;
+
+#[cfg(feature = "async")]
+pub use self::async_client::{AsyncBridgeClient, WalletMessageStream};
+#[cfg(feature = "blocking")]
+pub use self::blocking::{BlockingBridgeClient, WalletMessages};
+
+/// Default time-to-live of messages posted to the bridge, in seconds.
+pub const DEFAULT_TTL: u64 = 300;
+
+/// A decrypted message received from the wallet through the bridge.
+#[derive(Debug)]
+pub enum WalletMessage {
+    /// Event emitted by the wallet, e.g. connect or disconnect.
+    Event(WalletEvent),
+    /// Reply to an RPC request sent by the app.
+    Response(RawWalletResponse),
+}
+
+/// How a listener reconnects after the events stream is lost.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Delay before the first reconnection attempt, doubled on every following attempt.
+    pub initial_delay: Duration,
+    /// Upper bound for the delay between reconnection attempts.
+    pub max_delay: Duration,
+    /// Consecutive failed attempts after which the listener gives up, `None` to retry forever.
+    pub max_attempts: Option<u32>,
+    /// The stream is considered lost when nothing, not even a heartbeat, was received for this long.
+    pub heartbeat_timeout: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            max_attempts: None,
+            heartbeat_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Returns the delay before the given reconnection attempt, starting from 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.initial_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+
+    fn exhausted(&self, attempt: u32) -> bool {
+        self.max_attempts.is_some_and(|max| attempt > max)
+    }
+}
+
+/// Connection status of a bridge listener.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListenerStatus {
+    /// The events stream is open.
+    Connected,
+    /// The events stream was lost, the listener waits `delay` before reconnection `attempt`.
+    Reconnecting { attempt: u32, delay: Duration },
+    /// The listener gave up reconnecting.
+    Closed,
+}
+
+/// Progress of a bridge listener, kept across reconnections.
+#[derive(Debug, Clone)]
+pub struct ListenerState {
+    pub status: ListenerStatus,
+    /// Id of the last message event, sent as `last_event_id` on reconnection so the bridge replays anything missed.
+    /// Persist it to resume after a restart.
+    pub last_event_id: Option<String>,
+    /// When the bridge last sent a heartbeat.
+    pub last_heartbeat: Option<Instant>,
+    /// Client id of the wallet that sent the last message, the receiver of the app requests once connected.
+    pub last_sender: Option<String>,
+}
+
+impl ListenerState {
+    fn new(last_event_id: Option<String>) -> Self {
+        Self {
+            status: ListenerStatus::Connected,
+            last_event_id,
+            last_heartbeat: None,
+            last_sender: None,
+        }
+    }
+
+    /// Takes the next wallet message out of the decoded events, recording event ids and heartbeats on the way.
+    fn next_message(
+        &mut self,
+        decoder: &mut SseDecoder,
+        keypair: &ClientKeypair,
+    ) -> Option<Result<WalletMessage, Error>> {
+        while let Some(event) = decoder.next_event() {
+            match event.event.as_str() {
+                "message" => {
+                    if event.id.is_some() {
+                        self.last_event_id = event.id;
+                    }
+                    return Some(decode_wallet_message(keypair, &event.data).map(
+                        |(sender, wallet_message)| {
+                            self.last_sender = Some(sender);
+                            wallet_message
+                        },
+                    ));
+                }
+                "heartbeat" => self.last_heartbeat = Some(Instant::now()),
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// Creates the listen URL for the client, resuming after `last_event_id` if given.
+fn listen_url(
+    bridge_url: &str,
+    client_id: &str,
+    topics: &Option<Vec<Topic>>,
+    last_event_id: &Option<String>,
+) -> Result<String, Error> {
+    let url = create_listen_url(bridge_url, &vec![client_id.to_string()], topics)?;
+    let mut url = Url::parse(&url)?;
+    if let Some(last_event_id) = last_event_id {
+        url.query_pairs_mut()
+            .append_pair("last_event_id", last_event_id);
+    }
+    Ok(url.to_string())
+}
+
+/// Decodes the data of a bridge `message` event into the sender client id and a `WalletMessage`.
+fn decode_wallet_message(
+    keypair: &ClientKeypair,
+    data: &str,
+) -> Result<(String, WalletMessage), Error> {
+    let bridge_msg: BridgeMessage = serde_json::from_str(data)?;
+    let plaintext = open_from(keypair, &bridge_msg)?;
+    let value: serde_json::Value = serde_json::from_slice(&plaintext)?;
+    // Events carry an `event` field, RPC responses carry `result` or `error` instead.
+    let wallet_message = if value.get("event").is_some() {
+        WalletMessage::Event(serde_json::from_value(value)?)
+    } else {
+        WalletMessage::Response(serde_json::from_value(value)?)
+    };
+    Ok((bridge_msg.from, wallet_message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::seal_for;
+    use base64::{engine::general_purpose, Engine as _};
+
+    #[test]
+    fn test_reconnect_delay() {
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(5),
+            max_attempts: Some(3),
+            heartbeat_timeout: Duration::from_secs(30),
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(500));
+        assert_eq!(policy.delay(2), Duration::from_secs(1));
+        assert_eq!(policy.delay(4), Duration::from_secs(4));
+        assert_eq!(policy.delay(5), Duration::from_secs(5));
+        assert_eq!(policy.delay(100), Duration::from_secs(5));
+        assert!(!policy.exhausted(3));
+        assert!(policy.exhausted(4));
+    }
+
+    #[test]
+    fn test_listen_url_resume() {
+        let client_id = "a3baaa66a1eee1dbe79058aca5980a2222fcc418508635f9317e9dc8c3108201";
+        let url = listen_url(
+            "https://bridge.tonapi.io/bridge",
+            client_id,
+            &None,
+            &Some("1686".to_string()),
+        )
+        .unwrap();
+        assert_eq!(
+            url,
+            format!(
+                "https://bridge.tonapi.io/bridge/events?client_id={}&last_event_id=1686",
+                client_id
+            )
+        );
+    }
+
+    fn bridge_data(wallet: &ClientKeypair, app: &ClientKeypair, plaintext: &str) -> String {
+        let message = seal_for(wallet, &app.get_hex_public(), plaintext.as_bytes()).unwrap();
+        serde_json::to_string(&BridgeMessage {
+            from: wallet.get_hex_public(),
+            message,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_decode_event() {
+        let app = ClientKeypair::generate_random();
+        let wallet = ClientKeypair::generate_random();
+        let data = bridge_data(
+            &wallet,
+            &app,
+            "{\"event\":\"disconnect\",\"id\":3,\"payload\":{}}",
+        );
+
+        let (sender, decoded) = decode_wallet_message(&app, &data).unwrap();
+        assert_eq!(sender, wallet.get_hex_public());
+        assert!(matches!(
+            decoded,
+            WalletMessage::Event(WalletEvent::Disconnect { id: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_decode_response() {
+        let app = ClientKeypair::generate_random();
+        let wallet = ClientKeypair::generate_random();
+        let data = bridge_data(&wallet, &app, "{\"result\":\"te6cc\",\"id\":\"2\"}");
+
+        match decode_wallet_message(&app, &data).unwrap().1 {
+            WalletMessage::Response(response) => assert_eq!(response.id(), "2"),
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_short_message() {
+        let app = ClientKeypair::generate_random();
+        let data = serde_json::to_string(&BridgeMessage {
+            from: app.get_hex_public(),
+            message: general_purpose::STANDARD.encode([0u8; 8]),
+        })
+        .unwrap();
+        assert!(decode_wallet_message(&app, &data).is_err());
+    }
+}
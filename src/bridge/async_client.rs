@@ -0,0 +1,406 @@
+use super::sse::SseDecoder;
+use super::{listen_url, ListenerState, ListenerStatus, ReconnectPolicy, WalletMessage};
+use crate::crypto::{seal_for, ClientKeypair};
+use crate::helpers::create_message_url;
+use crate::types::Topic;
+use crate::Error;
+use bytes::Bytes;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::Client;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use tokio::time;
+
+/// Asynchronous client for the TON Connect HTTP bridge.
+///
+/// # Example
+///
+/// ```no_run
+/// use futures::StreamExt;
+/// use ton_connect::bridge::AsyncBridgeClient;
+/// use ton_connect::crypto::ClientKeypair;
+///
+/// # async fn run() -> Result<(), ton_connect::Error> {
+/// let keypair = ClientKeypair::generate_random();
+/// let client = AsyncBridgeClient::new("https://bridge.tonapi.io/bridge", keypair)?;
+/// let mut wallet_messages = client.listen(&None, None).await?;
+/// while let Some(wallet_message) = wallet_messages.next().await {
+///     println!("{:?}", wallet_message?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AsyncBridgeClient {
+    bridge_url: String,
+    keypair: Arc<ClientKeypair>,
+    http: Client,
+    reconnect_policy: ReconnectPolicy,
+}
+
+impl AsyncBridgeClient {
+    /// Creates a client talking to the bridge at `bridge_url` on behalf of `keypair`.
+    pub fn new(bridge_url: &str, keypair: ClientKeypair) -> Result<Self, Error> {
+        let http = Client::builder().build()?;
+        Ok(Self {
+            bridge_url: bridge_url.to_string(),
+            keypair: Arc::new(keypair),
+            http,
+            reconnect_policy: ReconnectPolicy::default(),
+        })
+    }
+
+    /// Sets how listeners created by this client reconnect.
+    pub fn with_reconnect_policy(mut self, reconnect_policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = reconnect_policy;
+        self
+    }
+
+    /// Returns the bridge URL the client talks to.
+    pub fn bridge_url(&self) -> &str {
+        &self.bridge_url
+    }
+
+    /// Returns the keypair the client uses as its bridge identity.
+    pub fn keypair(&self) -> &ClientKeypair {
+        &self.keypair
+    }
+
+    /// Encrypts `message` for the `receiver` client ID and posts it to the bridge.
+    pub async fn send_message(
+        &self,
+        receiver: &str,
+        message: &[u8],
+        ttl: u64,
+        topic: &Option<Topic>,
+    ) -> Result<(), Error> {
+        let url = create_message_url(
+            &self.bridge_url,
+            &self.keypair.get_hex_public(),
+            receiver,
+            ttl,
+            topic,
+        )?;
+        let body = seal_for(&self.keypair, receiver, message)?;
+        self.http
+            .post(url)
+            .header(CONTENT_TYPE, "text/plain")
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Subscribes to the bridge events addressed to this client and returns a stream of the decrypted wallet messages.
+    ///
+    /// Pass the `last_event_id` persisted from a previous listener to receive the messages sent in the meantime.
+    /// The stream reconnects according to the client's `ReconnectPolicy` whenever the events stream is lost.
+    pub async fn listen(
+        &self,
+        topics: &Option<Vec<Topic>>,
+        last_event_id: Option<String>,
+    ) -> Result<WalletMessageStream, Error> {
+        let state = Arc::new(Mutex::new(ListenerState::new(last_event_id)));
+        let mut listener = Listener {
+            client: self.clone(),
+            topics: topics.clone(),
+            body: None,
+            decoder: SseDecoder::new(),
+            state: state.clone(),
+            attempt: 0,
+        };
+        listener.body = Some(listener.connect().await?);
+        let messages = stream::unfold(listener, |mut listener| async move {
+            let wallet_message = listener.next_message().await?;
+            Some((wallet_message, listener))
+        });
+        Ok(WalletMessageStream {
+            messages: messages.boxed(),
+            state,
+        })
+    }
+}
+
+/// Stream of the wallet messages received from the bridge.
+///
+/// Ends only when the `ReconnectPolicy` gives up, after yielding the last connection error.
+pub struct WalletMessageStream {
+    messages: BoxStream<'static, Result<WalletMessage, Error>>,
+    state: Arc<Mutex<ListenerState>>,
+}
+
+impl WalletMessageStream {
+    /// Returns the connection status, the last event id and the last heartbeat time.
+    pub fn state(&self) -> ListenerState {
+        self.state.lock().expect("listener state poisoned").clone()
+    }
+}
+
+impl Stream for WalletMessageStream {
+    type Item = Result<WalletMessage, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.messages.poll_next_unpin(cx)
+    }
+}
+
+struct Listener {
+    client: AsyncBridgeClient,
+    topics: Option<Vec<Topic>>,
+    body: Option<BoxStream<'static, reqwest::Result<Bytes>>>,
+    decoder: SseDecoder,
+    state: Arc<Mutex<ListenerState>>,
+    attempt: u32,
+}
+
+impl Listener {
+    async fn connect(&mut self) -> Result<BoxStream<'static, reqwest::Result<Bytes>>, Error> {
+        let last_event_id = self.state().last_event_id.clone();
+        let url = listen_url(
+            &self.client.bridge_url,
+            &self.client.keypair.get_hex_public(),
+            &self.topics,
+            &last_event_id,
+        )?;
+        let response = self
+            .client
+            .http
+            .get(url)
+            .header(ACCEPT, "text/event-stream")
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.bytes_stream().boxed())
+    }
+
+    async fn next_message(&mut self) -> Option<Result<WalletMessage, Error>> {
+        let policy = self.client.reconnect_policy.clone();
+        loop {
+            let wallet_message = self
+                .state
+                .lock()
+                .expect("listener state poisoned")
+                .next_message(&mut self.decoder, &self.client.keypair);
+            if wallet_message.is_some() {
+                return wallet_message;
+            }
+
+            let body = match self.body.as_mut() {
+                Some(body) => body,
+                None => {
+                    if self.state().status == ListenerStatus::Closed {
+                        return None;
+                    }
+                    self.attempt += 1;
+                    if policy.exhausted(self.attempt) {
+                        self.state().status = ListenerStatus::Closed;
+                        return None;
+                    }
+                    let delay = policy.delay(self.attempt);
+                    self.state().status = ListenerStatus::Reconnecting {
+                        attempt: self.attempt,
+                        delay,
+                    };
+                    time::sleep(delay).await;
+                    match self.connect().await {
+                        Ok(body) => {
+                            self.state().status = ListenerStatus::Connected;
+                            self.body = Some(body);
+                        }
+                        Err(err) => {
+                            if policy.exhausted(self.attempt + 1) {
+                                self.state().status = ListenerStatus::Closed;
+                                return Some(Err(err));
+                            }
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            match time::timeout(policy.heartbeat_timeout, body.next()).await {
+                Ok(Some(Ok(chunk))) => {
+                    self.attempt = 0;
+                    self.decoder.push(&chunk);
+                }
+                // Closed by the bridge, timed out waiting for a heartbeat or failed.
+                _ => {
+                    self.body = None;
+                    self.decoder = SseDecoder::new();
+                }
+            }
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, ListenerState> {
+        self.state.lock().expect("listener state poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_bridge::{
+        message_event, write_events, write_events_head, write_status, StandInBridge,
+    };
+    use super::*;
+    use crate::types::{WalletEvent, WalletResponse};
+    use std::time::Duration;
+
+    fn fast_policy(max_attempts: Option<u32>) -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(10),
+            max_attempts,
+            heartbeat_timeout: Duration::from_millis(300),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_listen_streams_messages() {
+        let app = ClientKeypair::generate_random();
+        let wallet = ClientKeypair::generate_random();
+        let events = format!(
+            "event: heartbeat\n\n{}{}",
+            message_event(
+                &wallet,
+                &app,
+                1,
+                "{\"event\":\"disconnect\",\"id\":1,\"payload\":{}}"
+            ),
+            message_event(&wallet, &app, 2, "{\"result\":\"te6cc\",\"id\":\"5\"}"),
+        );
+        let bridge = StandInBridge::start(move |_, stream| write_events(stream, &events));
+
+        let client = AsyncBridgeClient::new(&bridge.url, app)
+            .unwrap()
+            .with_reconnect_policy(fast_policy(Some(0)));
+        let mut stream = client.listen(&None, None).await.unwrap();
+        let wallet_messages = stream.by_ref().collect::<Vec<_>>().await;
+        assert_eq!(wallet_messages.len(), 2);
+        assert!(matches!(
+            wallet_messages[0],
+            Ok(WalletMessage::Event(WalletEvent::Disconnect { id: 1 }))
+        ));
+        match &wallet_messages[1] {
+            Ok(WalletMessage::Response(WalletResponse::Success { result, .. })) => {
+                assert_eq!(result, "te6cc")
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+        let state = stream.state();
+        assert_eq!(state.status, ListenerStatus::Closed);
+        assert_eq!(state.last_event_id.as_deref(), Some("2"));
+        assert!(state.last_heartbeat.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_listen_resumes_after_disconnect() {
+        let app = ClientKeypair::generate_random();
+        let wallet = ClientKeypair::generate_random();
+        let first = message_event(&wallet, &app, 10, "{\"result\":\"first\",\"id\":\"1\"}");
+        let second = message_event(&wallet, &app, 11, "{\"result\":\"second\",\"id\":\"2\"}");
+        let mut connections = 0;
+        let bridge = StandInBridge::start(move |_, stream| {
+            connections += 1;
+            match connections {
+                1 => write_events(stream, &first),
+                2 => write_events(stream, &second),
+                _ => write_status(stream, "503 Service Unavailable"),
+            }
+        });
+
+        let app_public = app.get_hex_public();
+        let client = AsyncBridgeClient::new(&bridge.url, app)
+            .unwrap()
+            .with_reconnect_policy(fast_policy(Some(1)));
+        let mut stream = client.listen(&None, Some("9".to_string())).await.unwrap();
+        let results = stream.by_ref().collect::<Vec<_>>().await;
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_ok());
+        assert!(results[2].is_err());
+        assert_eq!(stream.state().status, ListenerStatus::Closed);
+
+        let targets = bridge
+            .requests
+            .try_iter()
+            .map(|request| request.target)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            targets,
+            vec![
+                format!("/bridge/events?client_id={}&last_event_id=9", app_public),
+                format!("/bridge/events?client_id={}&last_event_id=10", app_public),
+                format!("/bridge/events?client_id={}&last_event_id=11", app_public),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_listen_reconnects_on_heartbeat_timeout() {
+        let app = ClientKeypair::generate_random();
+        let wallet = ClientKeypair::generate_random();
+        let event = message_event(&wallet, &app, 7, "{\"result\":\"late\",\"id\":\"3\"}");
+        let mut connections = 0;
+        let bridge = StandInBridge::start(move |_, stream| {
+            connections += 1;
+            if connections == 1 {
+                // A heartbeat and then silence, without closing the connection.
+                write_events_head(stream, "event: heartbeat\n\n");
+                let stream = stream.try_clone().unwrap();
+                std::thread::spawn(move || {
+                    std::thread::sleep(Duration::from_secs(2));
+                    drop(stream);
+                });
+            } else {
+                write_events(stream, &event);
+            }
+        });
+
+        let client = AsyncBridgeClient::new(&bridge.url, app)
+            .unwrap()
+            .with_reconnect_policy(fast_policy(None));
+        let mut stream = client.listen(&None, None).await.unwrap();
+        match stream.next().await {
+            Some(Ok(WalletMessage::Response(WalletResponse::Success { result, .. }))) => {
+                assert_eq!(result, "late")
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+        let state = stream.state();
+        assert_eq!(state.status, ListenerStatus::Connected);
+        assert!(state.last_heartbeat.is_some());
+        assert_eq!(state.last_event_id.as_deref(), Some("7"));
+    }
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let bridge = StandInBridge::start(|_, stream| write_status(stream, "200 OK"));
+        let app = ClientKeypair::generate_random();
+        let wallet_public = ClientKeypair::generate_random().get_hex_public();
+        let app_public = app.get_hex_public();
+
+        let client = AsyncBridgeClient::new(&bridge.url, app).unwrap();
+        client
+            .send_message(&wallet_public, b"hello", 60, &None)
+            .await
+            .unwrap();
+
+        let request = bridge.requests.recv().unwrap();
+        assert_eq!(request.method, "POST");
+        assert!(request
+            .headers
+            .contains(&("content-type".to_string(), "text/plain".to_string())));
+        assert!(!request.body.is_empty());
+        assert_eq!(
+            request.target,
+            format!(
+                "/bridge/message?client_id={}&to={}&ttl=60",
+                app_public, wallet_public
+            )
+        );
+    }
+}
@@ -0,0 +1,438 @@
+use super::sse::SseDecoder;
+use super::{listen_url, ListenerState, ListenerStatus, ReconnectPolicy, WalletMessage};
+use crate::crypto::{seal_for, ClientKeypair};
+use crate::helpers::create_message_url;
+use crate::types::Topic;
+use crate::Error;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// Blocking client for the TON Connect HTTP bridge.
+///
+/// # Example
+///
+/// ```no_run
+/// use ton_connect::bridge::BlockingBridgeClient;
+/// use ton_connect::crypto::ClientKeypair;
+/// use ton_connect::types::Topic;
+///
+/// let keypair = ClientKeypair::generate_random();
+/// let client = BlockingBridgeClient::new("https://bridge.tonapi.io/bridge", keypair).unwrap();
+/// for wallet_message in client.listen(&Some(vec![Topic::SendTransaction]), None).unwrap() {
+///     println!("{:?}", wallet_message.unwrap());
+/// }
+/// ```
+#[derive(Clone)]
+pub struct BlockingBridgeClient {
+    bridge_url: String,
+    keypair: Arc<ClientKeypair>,
+    http: Client,
+    reconnect_policy: ReconnectPolicy,
+}
+
+impl BlockingBridgeClient {
+    /// Creates a client talking to the bridge at `bridge_url` on behalf of `keypair`.
+    pub fn new(bridge_url: &str, keypair: ClientKeypair) -> Result<Self, Error> {
+        let http = Client::builder().build()?;
+        Ok(Self {
+            bridge_url: bridge_url.to_string(),
+            keypair: Arc::new(keypair),
+            http,
+            reconnect_policy: ReconnectPolicy::default(),
+        })
+    }
+
+    /// Sets how listeners created by this client reconnect.
+    pub fn with_reconnect_policy(mut self, reconnect_policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = reconnect_policy;
+        self
+    }
+
+    /// Returns the bridge URL the client talks to.
+    pub fn bridge_url(&self) -> &str {
+        &self.bridge_url
+    }
+
+    /// Returns the keypair the client uses as its bridge identity.
+    pub fn keypair(&self) -> &ClientKeypair {
+        &self.keypair
+    }
+
+    /// Encrypts `message` for the `receiver` client ID and posts it to the bridge.
+    pub fn send_message(
+        &self,
+        receiver: &str,
+        message: &[u8],
+        ttl: u64,
+        topic: &Option<Topic>,
+    ) -> Result<(), Error> {
+        let url = create_message_url(
+            &self.bridge_url,
+            &self.keypair.get_hex_public(),
+            receiver,
+            ttl,
+            topic,
+        )?;
+        let body = seal_for(&self.keypair, receiver, message)?;
+        self.http
+            .post(url)
+            .header(CONTENT_TYPE, "text/plain")
+            .body(body)
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Subscribes to the bridge events addressed to this client and returns an iterator over the decrypted wallet messages.
+    ///
+    /// Pass the `last_event_id` persisted from a previous listener to receive the messages sent in the meantime.
+    /// The iterator reconnects according to the client's `ReconnectPolicy` whenever the stream is lost.
+    pub fn listen(
+        &self,
+        topics: &Option<Vec<Topic>>,
+        last_event_id: Option<String>,
+    ) -> Result<WalletMessages, Error> {
+        // The client level timeout applies to every read, so it detects missing heartbeats.
+        let http = Client::builder()
+            .timeout(self.reconnect_policy.heartbeat_timeout)
+            .build()?;
+        let mut wallet_messages = WalletMessages {
+            client: self.clone(),
+            http,
+            topics: topics.clone(),
+            response: None,
+            decoder: SseDecoder::new(),
+            state: ListenerState::new(last_event_id),
+            attempt: 0,
+            stop: None,
+        };
+        wallet_messages.response = Some(wallet_messages.connect()?);
+        Ok(wallet_messages)
+    }
+}
+
+/// Iterator over the wallet messages received from the bridge.
+///
+/// Ends when the `ReconnectPolicy` gives up, after yielding the last connection error, or once
+/// the stop flag given to `with_stop_flag` is set.
+pub struct WalletMessages {
+    client: BlockingBridgeClient,
+    http: Client,
+    topics: Option<Vec<Topic>>,
+    response: Option<Response>,
+    decoder: SseDecoder,
+    state: ListenerState,
+    attempt: u32,
+    stop: Option<Arc<AtomicBool>>,
+}
+
+impl WalletMessages {
+    /// Ends the iterator once `stop` is set, e.g. from another thread. The flag is checked after
+    /// every read, which the bridge heartbeats or the heartbeat timeout end, and before reconnecting.
+    pub fn with_stop_flag(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// Returns the connection status, the last event id and the last heartbeat time.
+    pub fn state(&self) -> &ListenerState {
+        &self.state
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop
+            .as_ref()
+            .is_some_and(|stop| stop.load(Ordering::SeqCst))
+    }
+
+    fn connect(&self) -> Result<Response, Error> {
+        let url = listen_url(
+            &self.client.bridge_url,
+            &self.client.keypair.get_hex_public(),
+            &self.topics,
+            &self.state.last_event_id,
+        )?;
+        let response = self
+            .http
+            .get(url)
+            .header(ACCEPT, "text/event-stream")
+            .send()?
+            .error_for_status()?;
+        Ok(response)
+    }
+}
+
+impl Iterator for WalletMessages {
+    type Item = Result<WalletMessage, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = [0u8; 4096];
+        loop {
+            if self.stopped() {
+                self.response = None;
+                self.state.status = ListenerStatus::Closed;
+                return None;
+            }
+            if let Some(wallet_message) = self
+                .state
+                .next_message(&mut self.decoder, &self.client.keypair)
+            {
+                return Some(wallet_message);
+            }
+
+            let response = match self.response.as_mut() {
+                Some(response) => response,
+                None => {
+                    if self.state.status == ListenerStatus::Closed {
+                        return None;
+                    }
+                    self.attempt += 1;
+                    if self.client.reconnect_policy.exhausted(self.attempt) {
+                        self.state.status = ListenerStatus::Closed;
+                        return None;
+                    }
+                    let delay = self.client.reconnect_policy.delay(self.attempt);
+                    self.state.status = ListenerStatus::Reconnecting {
+                        attempt: self.attempt,
+                        delay,
+                    };
+                    thread::sleep(delay);
+                    if self.stopped() {
+                        continue;
+                    }
+                    match self.connect() {
+                        Ok(response) => {
+                            self.state.status = ListenerStatus::Connected;
+                            self.response = Some(response);
+                        }
+                        Err(err) => {
+                            if self.client.reconnect_policy.exhausted(self.attempt + 1) {
+                                self.state.status = ListenerStatus::Closed;
+                                return Some(Err(err));
+                            }
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            match response.read(&mut chunk) {
+                Ok(read) if read > 0 => {
+                    self.attempt = 0;
+                    self.decoder.push(&chunk[..read]);
+                }
+                // Closed by the bridge, timed out waiting for a heartbeat or failed.
+                _ => {
+                    self.response = None;
+                    self.decoder = SseDecoder::new();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_bridge::{
+        message_event, write_events, write_events_head, write_status, StandInBridge,
+    };
+    use super::*;
+    use crate::crypto::open_from;
+    use crate::types::{BridgeMessage, WalletEvent, WalletResponse};
+    use std::time::Duration;
+
+    fn fast_policy(max_attempts: Option<u32>) -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(10),
+            max_attempts,
+            heartbeat_timeout: Duration::from_millis(300),
+        }
+    }
+
+    #[test]
+    fn test_listen_decrypts_messages() {
+        let app = ClientKeypair::generate_random();
+        let wallet = ClientKeypair::generate_random();
+        let events = format!(
+            "event: heartbeat\n\n{}",
+            message_event(
+                &wallet,
+                &app,
+                42,
+                "{\"event\":\"disconnect\",\"id\":1,\"payload\":{}}"
+            )
+        );
+        let bridge = StandInBridge::start(move |_, stream| write_events(stream, &events));
+
+        let app_public = app.get_hex_public();
+        let client = BlockingBridgeClient::new(&bridge.url, app).unwrap();
+        let mut wallet_messages = client
+            .listen(&Some(vec![Topic::SendTransaction]), None)
+            .unwrap();
+        assert!(matches!(
+            wallet_messages.next(),
+            Some(Ok(WalletMessage::Event(WalletEvent::Disconnect { id: 1 })))
+        ));
+        assert_eq!(wallet_messages.state().status, ListenerStatus::Connected);
+        assert_eq!(
+            wallet_messages.state().last_sender,
+            Some(wallet.get_hex_public())
+        );
+        assert_eq!(wallet_messages.state().last_event_id.as_deref(), Some("42"));
+        assert!(wallet_messages.state().last_heartbeat.is_some());
+
+        let request = bridge.requests.recv().unwrap();
+        assert_eq!(request.method, "GET");
+        assert!(request
+            .headers
+            .contains(&("accept".to_string(), "text/event-stream".to_string())));
+        assert_eq!(
+            request.target,
+            format!(
+                "/bridge/events?client_id={}&topic=sendTransaction",
+                app_public
+            )
+        );
+    }
+
+    #[test]
+    fn test_listen_resumes_after_disconnect() {
+        let app = ClientKeypair::generate_random();
+        let wallet = ClientKeypair::generate_random();
+        let first = message_event(&wallet, &app, 1, "{\"result\":\"first\",\"id\":\"1\"}");
+        let second = message_event(&wallet, &app, 2, "{\"result\":\"second\",\"id\":\"2\"}");
+        let mut connections = 0;
+        let bridge = StandInBridge::start(move |_, stream| {
+            connections += 1;
+            match connections {
+                1 => write_events(stream, &first),
+                2 => write_events(stream, &second),
+                _ => write_status(stream, "503 Service Unavailable"),
+            }
+        });
+
+        let app_public = app.get_hex_public();
+        let client = BlockingBridgeClient::new(&bridge.url, app)
+            .unwrap()
+            .with_reconnect_policy(fast_policy(Some(2)));
+        let mut wallet_messages = client.listen(&None, None).unwrap();
+        let results = wallet_messages.by_ref().collect::<Vec<_>>();
+        assert_eq!(results.len(), 3);
+        for (result, expected) in results.iter().zip(["first", "second"].iter()) {
+            match result {
+                Ok(WalletMessage::Response(WalletResponse::Success { result, .. })) => {
+                    assert_eq!(result, *expected)
+                }
+                other => panic!("unexpected message: {:?}", other),
+            }
+        }
+        assert!(results[2].is_err());
+        assert_eq!(wallet_messages.state().status, ListenerStatus::Closed);
+        assert_eq!(wallet_messages.state().last_event_id.as_deref(), Some("2"));
+
+        let targets = bridge
+            .requests
+            .try_iter()
+            .map(|request| request.target)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            targets,
+            vec![
+                format!("/bridge/events?client_id={}", app_public),
+                format!("/bridge/events?client_id={}&last_event_id=1", app_public),
+                format!("/bridge/events?client_id={}&last_event_id=2", app_public),
+                format!("/bridge/events?client_id={}&last_event_id=2", app_public),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_listen_reconnects_on_heartbeat_timeout() {
+        let app = ClientKeypair::generate_random();
+        let wallet = ClientKeypair::generate_random();
+        let event = message_event(&wallet, &app, 7, "{\"result\":\"late\",\"id\":\"3\"}");
+        let mut connections = 0;
+        let bridge = StandInBridge::start(move |_, stream| {
+            connections += 1;
+            if connections == 1 {
+                // A heartbeat and then silence, without closing the connection.
+                write_events_head(stream, "event: heartbeat\n\n");
+                let stream = stream.try_clone().unwrap();
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_secs(2));
+                    drop(stream);
+                });
+            } else {
+                write_events(stream, &event);
+            }
+        });
+
+        let client = BlockingBridgeClient::new(&bridge.url, app)
+            .unwrap()
+            .with_reconnect_policy(fast_policy(None));
+        let mut wallet_messages = client.listen(&None, Some("5".to_string())).unwrap();
+        match wallet_messages.next() {
+            Some(Ok(WalletMessage::Response(WalletResponse::Success { result, .. }))) => {
+                assert_eq!(result, "late")
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+        assert!(wallet_messages.state().last_heartbeat.is_some());
+        assert_eq!(wallet_messages.state().last_event_id.as_deref(), Some("7"));
+        assert!(bridge
+            .requests
+            .recv()
+            .unwrap()
+            .target
+            .ends_with("&last_event_id=5"));
+        assert!(bridge
+            .requests
+            .recv()
+            .unwrap()
+            .target
+            .ends_with("&last_event_id=5"));
+    }
+
+    #[test]
+    fn test_send_message() {
+        let bridge = StandInBridge::start(|_, stream| write_status(stream, "200 OK"));
+        let app = ClientKeypair::generate_random();
+        let wallet = ClientKeypair::generate_random();
+        let app_public = app.get_hex_public();
+        let wallet_public = wallet.get_hex_public();
+
+        let client = BlockingBridgeClient::new(&bridge.url, app).unwrap();
+        client
+            .send_message(&wallet_public, b"hello", 60, &Some(Topic::SignData))
+            .unwrap();
+
+        let request = bridge.requests.recv().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(
+            request.target,
+            format!(
+                "/bridge/message?client_id={}&to={}&ttl=60&topic=signData",
+                app_public, wallet_public
+            )
+        );
+        let bridge_message = BridgeMessage {
+            from: app_public,
+            message: request.body,
+        };
+        assert_eq!(open_from(&wallet, &bridge_message).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_send_message_rejected() {
+        let bridge = StandInBridge::start(|_, stream| write_status(stream, "400 Bad Request"));
+        let client =
+            BlockingBridgeClient::new(&bridge.url, ClientKeypair::generate_random()).unwrap();
+        let receiver = ClientKeypair::generate_random().get_hex_public();
+        assert!(client.send_message(&receiver, b"hello", 60, &None).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::VecDeque;
+
+/// Longest line and event data the decoder keeps, in bytes. Longer events are dropped.
+pub const MAX_EVENT_SIZE: usize = 1024 * 1024;
+
+/// A single event dispatched from a `text/event-stream` response.
+#[derive(Debug, PartialEq)]
+pub struct SseEvent {
+    /// Value of the last `id` field, if the bridge sent one.
+    pub id: Option<String>,
+    /// Event type, `message` unless the bridge sent an `event` field.
+    pub event: String,
+    /// Data lines joined with `\n`.
+    pub data: String,
+}
+
+/// Incremental decoder for server-sent events.
+///
+/// Bytes are pushed as they arrive from the network, complete events are
+/// queued and can be taken with `next_event`. Events over `MAX_EVENT_SIZE` are dropped.
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: Vec<u8>,
+    id: Option<String>,
+    event: Option<String>,
+    data: Option<String>,
+    events: VecDeque<SseEvent>,
+    // The start of the current line was dropped for being too long.
+    truncated: bool,
+    // The current event went over the size limit.
+    oversized: bool,
+}
+
+impl SseDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds a chunk of the response body into the decoder.
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+        while let Some(position) = self.buffer.iter().position(|&byte| byte == b'\n') {
+            let mut line: Vec<u8> = self.buffer.drain(..=position).collect();
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            if self.truncated {
+                self.truncated = false;
+                continue;
+            }
+            self.process_line(&String::from_utf8_lossy(&line));
+        }
+        if self.buffer.len() > MAX_EVENT_SIZE {
+            self.buffer.clear();
+            self.truncated = true;
+            self.oversized = true;
+        }
+    }
+
+    /// Takes the next complete event, if any.
+    pub fn next_event(&mut self) -> Option<SseEvent> {
+        self.events.pop_front()
+    }
+
+    fn process_line(&mut self, line: &str) {
+        if line.is_empty() {
+            self.dispatch();
+            return;
+        }
+        if line.starts_with(':') {
+            return;
+        }
+
+        let (field, value) = match line.find(':') {
+            Some(position) => {
+                let value = &line[position + 1..];
+                (&line[..position], value.strip_prefix(' ').unwrap_or(value))
+            }
+            None => (line, ""),
+        };
+
+        match field {
+            "id" => self.id = Some(value.to_string()),
+            "event" => self.event = Some(value.to_string()),
+            "data" => match self.data.as_mut() {
+                Some(data) if data.len() + value.len() >= MAX_EVENT_SIZE => self.oversized = true,
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => self.data = Some(value.to_string()),
+            },
+            _ => {}
+        }
+    }
+
+    fn dispatch(&mut self) {
+        let event = self.event.take();
+        let id = self.id.take();
+        let data = self.data.take();
+        if std::mem::take(&mut self.oversized) {
+            return;
+        }
+        // The bridge sends its heartbeats without data, they are dispatched all the same.
+        let data = match (data, event.as_deref()) {
+            (Some(data), _) => data,
+            (None, Some("heartbeat")) => String::new(),
+            (None, _) => return,
+        };
+        self.events.push_back(SseEvent {
+            id,
+            event: event.unwrap_or_else(|| "message".to_string()),
+            data,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_events() {
+        let mut decoder = SseDecoder::new();
+        decoder.push(b"event: heartbeat\r\ndata: heartbeat\r\n\r\n: comment\n");
+        decoder.push(b"id: 1686\nevent: message\ndata: {\"from\":");
+        assert_eq!(
+            decoder.next_event(),
+            Some(SseEvent {
+                id: None,
+                event: "heartbeat".to_string(),
+                data: "heartbeat".to_string(),
+            })
+        );
+        assert_eq!(decoder.next_event(), None);
+
+        decoder.push(b"\"abc\"}\n\ndata:first\ndata:second\n\n");
+        assert_eq!(
+            decoder.next_event(),
+            Some(SseEvent {
+                id: Some("1686".to_string()),
+                event: "message".to_string(),
+                data: "{\"from\":\"abc\"}".to_string(),
+            })
+        );
+        assert_eq!(
+            decoder.next_event(),
+            Some(SseEvent {
+                id: None,
+                event: "message".to_string(),
+                data: "first\nsecond".to_string(),
+            })
+        );
+        assert_eq!(decoder.next_event(), None);
+    }
+
+    #[test]
+    fn test_decode_heartbeat_without_data() {
+        let mut decoder = SseDecoder::new();
+        decoder.push(b"event: heartbeat\n\nevent: other\n\nevent: heartbeat\ndata: \n\n");
+        for _ in 0..2 {
+            assert_eq!(
+                decoder.next_event(),
+                Some(SseEvent {
+                    id: None,
+                    event: "heartbeat".to_string(),
+                    data: String::new(),
+                })
+            );
+        }
+        assert_eq!(decoder.next_event(), None);
+    }
+
+    #[test]
+    fn test_drop_oversized_events() {
+        let mut decoder = SseDecoder::new();
+        decoder.push(b"id: 1\ndata: ");
+        for _ in 0..=MAX_EVENT_SIZE / 4096 {
+            decoder.push(&[b'a'; 4096]);
+        }
+        assert!(decoder.buffer.len() <= MAX_EVENT_SIZE);
+        decoder.push(b"aaa\n\nid: 2\ndata: small\n\n");
+        assert_eq!(
+            decoder.next_event(),
+            Some(SseEvent {
+                id: Some("2".to_string()),
+                event: "message".to_string(),
+                data: "small".to_string(),
+            })
+        );
+        assert_eq!(decoder.next_event(), None);
+    }
+}
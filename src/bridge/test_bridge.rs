@@ -0,0 +1,142 @@
+//! Stand-in HTTP bridge for tests.
+
+use crate::crypto::{seal_for, ClientKeypair};
+use crate::types::BridgeMessage;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+/// A request received by the stand-in bridge.
+#[derive(Clone)]
+pub struct Request {
+    pub method: String,
+    pub target: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+/// Serves one canned response per connection on a local port and reports every received request.
+pub struct StandInBridge {
+    pub url: String,
+    pub requests: Receiver<Request>,
+}
+
+impl StandInBridge {
+    pub fn start<F>(mut respond: F) -> Self
+    where
+        F: FnMut(&Request, &mut TcpStream) + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/bridge", listener.local_addr().unwrap());
+        let (sender, requests) = channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => break,
+                };
+                let request = match read_request(&mut stream) {
+                    Some(request) => request,
+                    None => continue,
+                };
+                // Reported before responding, so the client never sees a response ahead of it.
+                let reported = sender.send(request.clone());
+                respond(&request, &mut stream);
+                if reported.is_err() {
+                    break;
+                }
+            }
+        });
+        Self { url, requests }
+    }
+}
+
+/// Formats a bridge `message` event carrying `plaintext` encrypted from `wallet` to `app`.
+pub fn message_event(
+    wallet: &ClientKeypair,
+    app: &ClientKeypair,
+    id: u64,
+    plaintext: &str,
+) -> String {
+    message_event_to(wallet, &app.get_hex_public(), id, plaintext)
+}
+
+/// Formats a bridge `message` event carrying `plaintext` encrypted from `wallet` to the `receiver` client id.
+pub fn message_event_to(
+    wallet: &ClientKeypair,
+    receiver: &str,
+    id: u64,
+    plaintext: &str,
+) -> String {
+    let message = seal_for(wallet, receiver, plaintext.as_bytes()).unwrap();
+    let data = serde_json::to_string(&BridgeMessage {
+        from: wallet.get_hex_public(),
+        message,
+    })
+    .unwrap();
+    format!("id: {}\nevent: message\ndata: {}\n\n", id, data)
+}
+
+/// Writes the head of an SSE response followed by the given raw events, leaving the connection open.
+pub fn write_events_head(stream: &mut TcpStream, events: &str) {
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\n{}",
+        events
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
+
+/// Writes an SSE response with the given raw event stream and closes the connection.
+pub fn write_events(stream: &mut TcpStream, events: &str) {
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n{}",
+        events
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
+
+/// Writes an empty response with the given status line.
+pub fn write_status(stream: &mut TcpStream, status: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
+
+fn read_request(stream: &mut TcpStream) -> Option<Request> {
+    let mut reader = BufReader::new(stream.try_clone().ok()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).ok()?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?.to_string();
+
+    let mut headers = Vec::new();
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_at(line.find(':')?);
+        let name = name.to_ascii_lowercase();
+        let value = value[1..].trim().to_string();
+        if name == "content-length" {
+            content_length = value.parse().ok()?;
+        }
+        headers.push((name, value));
+    }
+
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body).ok()?;
+    Some(Request {
+        method,
+        target,
+        headers,
+        body: String::from_utf8(body).ok()?,
+    })
+}
@@ -0,0 +1,629 @@
+//! TON Connect HTTP bridge server, to self-host a bridge or run the whole flow on localhost.
+//!
+//! Serves `GET <path>/events`, the server-sent events stream of the messages sent to the
+//! `client_id` list, resumed after `last_event_id`, and `POST <path>/message`, which stores
+//! the body for the `to` client during `ttl` seconds. The bridge URL to give to the clients
+//! is `http://<address><path>`.
+//!
+//! Client ids must be hex public keys, and the `BridgeLimits` of the config cap the message
+//! sizes, TTLs, posting rates, open streams and client ids per stream; the rejected requests
+//! are counted in `Rejections`.
+
+use crate::helpers::is_client_id;
+use crate::types::BridgeMessage;
+use crate::Error;
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{CACHE_CONTROL, CONTENT_TYPE};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, TcpListener};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::broadcast;
+use tokio::time;
+
+pub mod conformance;
+mod limits;
+mod store;
+
+pub use self::limits::{BridgeLimits, RateLimit, Rejections};
+use self::limits::{RateLimiter, Rejection, SubscriptionLimiter};
+pub use self::store::{MemoryMessageStore, MessageStore, StoreFuture, StoredMessage};
+
+/// Settings of a `BridgeServer`.
+#[derive(Debug, Clone)]
+pub struct BridgeServerConfig {
+    /// Path the endpoints are served under, e.g. `/bridge`.
+    pub path: String,
+    /// Interval between the heartbeats sent on every events stream.
+    pub heartbeat_interval: Duration,
+    /// Interval between the removals of expired messages.
+    pub expire_interval: Duration,
+    pub limits: BridgeLimits,
+}
+
+impl Default for BridgeServerConfig {
+    fn default() -> Self {
+        Self {
+            path: "/bridge".to_string(),
+            heartbeat_interval: Duration::from_secs(15),
+            expire_interval: Duration::from_secs(10),
+            limits: BridgeLimits::default(),
+        }
+    }
+}
+
+/// TON Connect HTTP bridge server, keeping the messages in a `MessageStore`.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::net::TcpListener;
+/// use ton_connect::bridge_server::{BridgeServer, BridgeServerConfig};
+///
+/// # async fn run() -> Result<(), ton_connect::Error> {
+/// let listener = TcpListener::bind("127.0.0.1:8081")?;
+/// // The clients use "http://127.0.0.1:8081/bridge" as bridge URL.
+/// BridgeServer::new(BridgeServerConfig::default()).run(listener).await
+/// # }
+/// ```
+pub struct BridgeServer {
+    config: BridgeServerConfig,
+    store: Arc<dyn MessageStore>,
+    rejections: Arc<Rejections>,
+}
+
+impl BridgeServer {
+    /// Creates a server keeping the messages in memory.
+    pub fn new(config: BridgeServerConfig) -> Self {
+        Self::with_store(config, Arc::new(MemoryMessageStore::new()))
+    }
+
+    /// Creates a server keeping the messages in `store`, possibly shared with other servers.
+    pub fn with_store(config: BridgeServerConfig, store: Arc<dyn MessageStore>) -> Self {
+        Self {
+            config,
+            store,
+            rejections: Arc::default(),
+        }
+    }
+
+    /// Counters of the rejected requests, updated while the server runs.
+    pub fn rejections(&self) -> Arc<Rejections> {
+        self.rejections.clone()
+    }
+
+    /// Serves the bridge on `listener` until the server fails. Must run within a Tokio runtime.
+    pub async fn run(self, listener: TcpListener) -> Result<(), Error> {
+        listener.set_nonblocking(true)?;
+        let limits = &self.config.limits;
+        let shared = Arc::new(Shared {
+            messages_per_ip: RateLimiter::new(limits.messages_per_ip),
+            messages_per_client: RateLimiter::new(limits.messages_per_client),
+            subscriptions_per_ip: SubscriptionLimiter::new(limits.subscriptions_per_ip),
+            subscriptions_per_client: SubscriptionLimiter::new(limits.subscriptions_per_client),
+            config: self.config,
+            store: self.store,
+            rejections: self.rejections,
+        });
+
+        let expiring = shared.clone();
+        let expire_task = tokio::spawn(async move {
+            let mut interval = time::interval(expiring.config.expire_interval);
+            loop {
+                interval.tick().await;
+                // A failed expiry is retried on the next tick.
+                let _ = expiring.store.expire(SystemTime::now()).await;
+                expiring.messages_per_ip.prune(Instant::now());
+                expiring.messages_per_client.prune(Instant::now());
+            }
+        });
+
+        let make_service = make_service_fn(move |connection: &AddrStream| {
+            let shared = shared.clone();
+            let ip = connection.remote_addr().ip();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let shared = shared.clone();
+                    async move { Ok::<_, Infallible>(shared.handle(request, ip).await) }
+                }))
+            }
+        });
+        let result = Server::from_tcp(listener)?.serve(make_service).await;
+        expire_task.abort();
+        Ok(result?)
+    }
+}
+
+struct Shared {
+    config: BridgeServerConfig,
+    store: Arc<dyn MessageStore>,
+    rejections: Arc<Rejections>,
+    messages_per_ip: RateLimiter,
+    messages_per_client: RateLimiter,
+    subscriptions_per_ip: SubscriptionLimiter,
+    subscriptions_per_client: SubscriptionLimiter,
+}
+
+impl Shared {
+    async fn handle(self: Arc<Self>, request: Request<Body>, ip: IpAddr) -> Response<Body> {
+        let endpoint = request
+            .uri()
+            .path()
+            .strip_prefix(self.config.path.trim_end_matches('/'))
+            .unwrap_or_default()
+            .to_string();
+        let query: HashMap<String, String> = request
+            .uri()
+            .query()
+            .map(|query| {
+                url::form_urlencoded::parse(query.as_bytes())
+                    .into_owned()
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let result = match (request.method(), endpoint.as_str()) {
+            (&Method::GET, "/events") => self.events(&query, ip).await,
+            (&Method::POST, "/message") => self.message(&query, ip, request.into_body()).await,
+            (_, "/events") | (_, "/message") => {
+                Err((StatusCode::METHOD_NOT_ALLOWED, "method not allowed"))
+            }
+            _ => Err((StatusCode::NOT_FOUND, "not found")),
+        };
+        result.unwrap_or_else(|(status, message)| json_response(status, message))
+    }
+
+    /// Opens the events stream of the clients, starting with the messages they missed.
+    async fn events(
+        self: Arc<Self>,
+        query: &HashMap<String, String>,
+        ip: IpAddr,
+    ) -> Result<Response<Body>, (StatusCode, &'static str)> {
+        let mut clients_ids: Vec<String> = query
+            .get("client_id")
+            .map(|ids| {
+                ids.split(',')
+                    .filter(|id| !id.is_empty())
+                    .map(str::to_lowercase)
+                    .collect()
+            })
+            .unwrap_or_default();
+        if clients_ids.is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "client_id is required"));
+        }
+        if clients_ids.len() > self.config.limits.max_clients_per_subscription {
+            return Err(self.reject(Rejection::TooManyClients));
+        }
+        if !clients_ids.iter().all(|id| is_client_id(id)) {
+            return Err(self.reject(Rejection::InvalidClientId));
+        }
+        clients_ids.sort();
+        clients_ids.dedup();
+        let last_event_id = match query.get("last_event_id") {
+            Some(id) => Some(
+                id.parse::<u64>()
+                    .map_err(|_| (StatusCode::BAD_REQUEST, "invalid last_event_id"))?,
+            ),
+            None => None,
+        };
+
+        let subscriptions = (
+            self.subscriptions_per_ip.acquire(&[ip.to_string()]),
+            self.subscriptions_per_client.acquire(&clients_ids),
+        );
+        let subscriptions = match subscriptions {
+            (Some(per_ip), Some(per_client)) => (per_ip, per_client),
+            _ => return Err(self.reject(Rejection::TooManySubscriptions)),
+        };
+
+        // Subscribe before reading the store, so no message falls in between.
+        let mut receiver = self.store.subscribe().await.map_err(store_failed)?;
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            // Counted as open until the stream ends.
+            let _subscriptions = subscriptions;
+            let mut last_sent = last_event_id;
+            let mut heartbeat = time::interval(self.config.heartbeat_interval);
+            let mut missed = true;
+            loop {
+                if missed {
+                    missed = false;
+                    let messages = match self
+                        .store
+                        .read_since(&clients_ids, last_sent, SystemTime::now())
+                        .await
+                    {
+                        Ok(messages) => messages,
+                        Err(_) => return,
+                    };
+                    for message in messages {
+                        last_sent = Some(message.id);
+                        if sender.send_data(message_event(&message)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                tokio::select! {
+                    received = receiver.recv() => match received {
+                        Ok(message) => {
+                            let is_new = last_sent.is_none_or(|last| message.id > last);
+                            if is_new && clients_ids.contains(&message.to) {
+                                last_sent = Some(message.id);
+                                if sender.send_data(message_event(&message)).await.is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                        // Too many messages at once, catch up from the store.
+                        Err(broadcast::error::RecvError::Lagged(_)) => missed = true,
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    },
+                    _ = heartbeat.tick() => {
+                        let event = Bytes::from_static(b"event: heartbeat\ndata: \n\n");
+                        if sender.send_data(event).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(Response::builder()
+            .header(CONTENT_TYPE, "text/event-stream")
+            .header(CACHE_CONTROL, "no-cache")
+            .body(body)
+            .expect("valid events response"))
+    }
+
+    /// Stores the message posted by `client_id` for `to` and hands it to the open events streams.
+    async fn message(
+        &self,
+        query: &HashMap<String, String>,
+        ip: IpAddr,
+        body: Body,
+    ) -> Result<Response<Body>, (StatusCode, &'static str)> {
+        let from = query
+            .get("client_id")
+            .ok_or((StatusCode::BAD_REQUEST, "client_id is required"))?
+            .to_lowercase();
+        let to = query
+            .get("to")
+            .ok_or((StatusCode::BAD_REQUEST, "to is required"))?
+            .to_lowercase();
+        let ttl = query
+            .get("ttl")
+            .ok_or((StatusCode::BAD_REQUEST, "ttl is required"))?
+            .parse::<u64>()
+            .map_err(|_| (StatusCode::BAD_REQUEST, "invalid ttl"))?;
+        if !is_client_id(&from) || !is_client_id(&to) {
+            return Err(self.reject(Rejection::InvalidClientId));
+        }
+        if ttl > self.config.limits.max_ttl {
+            return Err(self.reject(Rejection::TtlTooLong));
+        }
+        let now = Instant::now();
+        if !self.messages_per_ip.check(&ip.to_string(), now)
+            || !self.messages_per_client.check(&from, now)
+        {
+            return Err(self.reject(Rejection::RateLimited));
+        }
+        let body = read_body(body, self.config.limits.max_message_size)
+            .await
+            .map_err(|_| (StatusCode::BAD_REQUEST, "cannot read the body"))?
+            .ok_or_else(|| self.reject(Rejection::MessageTooLarge))?;
+        let message = std::str::from_utf8(&body)
+            .map_err(|_| (StatusCode::BAD_REQUEST, "invalid message"))?
+            .trim();
+        if message.is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "message is empty"));
+        }
+
+        let expires_at = SystemTime::now()
+            .checked_add(Duration::from_secs(ttl))
+            .ok_or((StatusCode::BAD_REQUEST, "invalid ttl"))?;
+        self.store
+            .append(&from, &to, message, query.get("topic").cloned(), expires_at)
+            .await
+            .map_err(store_failed)?;
+        Ok(json_response(StatusCode::OK, "OK"))
+    }
+
+    /// Counts the rejection and returns the response status and message.
+    fn reject(&self, rejection: Rejection) -> (StatusCode, &'static str) {
+        self.rejections.count(rejection);
+        match rejection {
+            Rejection::InvalidClientId => (StatusCode::BAD_REQUEST, "invalid client id"),
+            Rejection::MessageTooLarge => (StatusCode::PAYLOAD_TOO_LARGE, "message is too large"),
+            Rejection::TtlTooLong => (StatusCode::BAD_REQUEST, "ttl is too long"),
+            Rejection::RateLimited => (StatusCode::TOO_MANY_REQUESTS, "too many messages"),
+            Rejection::TooManySubscriptions => {
+                (StatusCode::TOO_MANY_REQUESTS, "too many subscriptions")
+            }
+            Rejection::TooManyClients => (StatusCode::BAD_REQUEST, "too many client ids"),
+        }
+    }
+}
+
+/// Reads the body, or `None` when it is over `max_size` bytes.
+async fn read_body(mut body: Body, max_size: usize) -> Result<Option<Vec<u8>>, hyper::Error> {
+    if body.size_hint().lower() > max_size as u64 {
+        return Ok(None);
+    }
+    let mut content = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if content.len() + chunk.len() > max_size {
+            return Ok(None);
+        }
+        content.extend_from_slice(&chunk);
+    }
+    Ok(Some(content))
+}
+
+fn store_failed(_: Error) -> (StatusCode, &'static str) {
+    (StatusCode::INTERNAL_SERVER_ERROR, "message store failed")
+}
+
+/// Formats a stored message as an SSE `message` event.
+fn message_event(message: &StoredMessage) -> Bytes {
+    let data = serde_json::to_string(&BridgeMessage {
+        from: message.from.clone(),
+        message: message.message.clone(),
+    })
+    .expect("bridge message serializes");
+    Bytes::from(format!(
+        "id: {}\nevent: message\ndata: {}\n\n",
+        message.id, data
+    ))
+}
+
+/// Response in the format of the reference bridge: `{"message": ..., "statusCode": ...}`.
+fn json_response(status: StatusCode, message: &str) -> Response<Body> {
+    let body = serde_json::json!({ "message": message, "statusCode": status.as_u16() });
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .expect("valid JSON response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APP: &str = "a3baaa66a1eee1dbe79058aca5980a2222fcc418508635f9317e9dc8c3108201";
+    const WALLET: &str = "ac02417ce252b719c9fea60404a5b34edddb3b5dd5b1d6a45764b34ce92eb33b";
+    const OTHER: &str = "23abbf5a0e9b037d47b0a345cee856962b7cdb2022f7edb0b4b613434f022128";
+
+    /// Starts a server on a free local port and returns its bridge URL.
+    fn start(config: BridgeServerConfig) -> (String, Arc<Rejections>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/bridge", listener.local_addr().unwrap());
+        let server = BridgeServer::new(config);
+        let rejections = server.rejections();
+        tokio::spawn(server.run(listener));
+        (url, rejections)
+    }
+
+    /// Reads the events stream until `pattern` appears, returning everything read.
+    async fn read_until(response: &mut reqwest::Response, pattern: &str) -> String {
+        let mut events = String::new();
+        while !events.contains(pattern) {
+            let chunk = time::timeout(Duration::from_secs(5), response.chunk())
+                .await
+                .expect("events timed out")
+                .unwrap()
+                .expect("events stream ended");
+            events.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+        events
+    }
+
+    async fn post(url: &str, from: &str, to: &str, ttl: u64, message: &str) -> u16 {
+        reqwest::Client::new()
+            .post(format!(
+                "{}/message?client_id={}&to={}&ttl={}",
+                url, from, to, ttl
+            ))
+            .body(message.to_string())
+            .send()
+            .await
+            .unwrap()
+            .status()
+            .as_u16()
+    }
+
+    #[tokio::test]
+    async fn test_message_and_events() {
+        let (url, rejections) = start(BridgeServerConfig {
+            heartbeat_interval: Duration::from_millis(50),
+            ..BridgeServerConfig::default()
+        });
+        let http = reqwest::Client::new();
+        let response = http
+            .post(format!(
+                "{}/message?client_id={}&to={}&ttl=300&topic=sendTransaction",
+                url, APP, WALLET
+            ))
+            .body("bWVzc2FnZQ==")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.text().await.unwrap(),
+            "{\"message\":\"OK\",\"statusCode\":200}"
+        );
+
+        let mut events = http
+            .get(format!("{}/events?client_id={},{}", url, OTHER, WALLET))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(events.headers()[CONTENT_TYPE], "text/event-stream");
+        let read = read_until(&mut events, "\"message\":\"bWVzc2FnZQ==\"").await;
+        assert!(read.contains(&format!("event: message\ndata: {{\"from\":\"{}\"", APP)));
+        let id: u64 = read
+            .lines()
+            .find_map(|line| line.strip_prefix("id: "))
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        // Messages posted while listening are streamed right away.
+        assert_eq!(post(&url, APP, WALLET, 300, "c2Vjb25k").await, 200);
+        assert_eq!(post(&url, WALLET, APP, 300, "b3RoZXI=").await, 200);
+        let read = read_until(&mut events, "c2Vjb25k").await;
+        assert!(!read.contains("b3RoZXI="));
+
+        // Resuming after the first message skips it.
+        let mut resumed = http
+            .get(format!(
+                "{}/events?client_id={}&last_event_id={}",
+                url, WALLET, id
+            ))
+            .send()
+            .await
+            .unwrap();
+        let read = read_until(&mut resumed, "c2Vjb25k").await;
+        assert!(!read.contains("bWVzc2FnZQ=="));
+        read_until(&mut resumed, "event: heartbeat\ndata: \n\n").await;
+        assert_eq!(rejections.total(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_bad_requests() {
+        let (url, rejections) = start(BridgeServerConfig::default());
+        let http = reqwest::Client::new();
+        let status = |request: reqwest::RequestBuilder| async move {
+            request.send().await.unwrap().status().as_u16()
+        };
+        assert_eq!(status(http.get(format!("{}/events", url))).await, 400);
+        assert_eq!(
+            status(http.get(format!("{}/events?client_id={}&last_event_id=x", url, APP))).await,
+            400
+        );
+        assert_eq!(
+            status(
+                http.post(format!("{}/message?client_id={}&to={}", url, APP, WALLET))
+                    .body("bWVzc2FnZQ==")
+            )
+            .await,
+            400
+        );
+        assert_eq!(post(&url, APP, WALLET, 60, "").await, 400);
+        assert_eq!(status(http.get(format!("{}/message", url))).await, 405);
+        assert_eq!(status(http.get(format!("{}/other", url))).await, 404);
+        assert_eq!(rejections.total(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_limits() {
+        let (url, rejections) = start(BridgeServerConfig {
+            heartbeat_interval: Duration::from_millis(20),
+            limits: BridgeLimits {
+                max_message_size: 8,
+                max_ttl: 60,
+                messages_per_ip: RateLimit::new(4, Duration::from_secs(60)),
+                messages_per_client: RateLimit::new(2, Duration::from_secs(60)),
+                subscriptions_per_ip: 2,
+                subscriptions_per_client: 1,
+                max_clients_per_subscription: 2,
+            },
+            ..BridgeServerConfig::default()
+        });
+        let http = reqwest::Client::new();
+
+        assert_eq!(post(&url, "app", WALLET, 60, "bWVz").await, 400);
+        assert_eq!(post(&url, APP, &WALLET[1..], 60, "bWVz").await, 400);
+        assert_eq!(
+            http.get(format!("{}/events?client_id={},app", url, WALLET))
+                .send()
+                .await
+                .unwrap()
+                .status(),
+            400
+        );
+        assert_eq!(rejections.invalid_client_id(), 3);
+
+        assert_eq!(post(&url, APP, WALLET, 61, "bWVz").await, 400);
+        assert_eq!(rejections.ttl_too_long(), 1);
+        assert_eq!(post(&url, APP, WALLET, 60, "bWVzc2FnZQ==").await, 413);
+        assert_eq!(rejections.message_too_large(), 1);
+
+        // The oversized message used a token of the app and of the IP address.
+        assert_eq!(post(&url, APP, WALLET, 60, "bWVz").await, 200);
+        assert_eq!(post(&url, APP, WALLET, 60, "bWVz").await, 429);
+        assert_eq!(post(&url, WALLET, APP, 60, "bWVz").await, 200);
+        assert_eq!(post(&url, OTHER, APP, 60, "bWVz").await, 429);
+        assert_eq!(rejections.rate_limited(), 2);
+
+        let subscribe = |clients_ids: String| {
+            let request = http.get(format!("{}/events?client_id={}", url, clients_ids));
+            async move { request.send().await.unwrap() }
+        };
+        let wallet = subscribe(WALLET.to_uppercase()).await;
+        assert_eq!(wallet.status(), 200);
+        assert_eq!(subscribe(format!("{},{}", APP, WALLET)).await.status(), 429);
+        let app = subscribe(APP.to_string()).await;
+        assert_eq!(app.status(), 200);
+        assert_eq!(subscribe(OTHER.to_string()).await.status(), 429);
+        assert_eq!(rejections.too_many_subscriptions(), 2);
+        assert_eq!(
+            subscribe(format!("{},{},{}", APP, WALLET, OTHER))
+                .await
+                .status(),
+            400
+        );
+        assert_eq!(rejections.too_many_clients(), 1);
+
+        // Closed streams no longer count, once a heartbeat fails.
+        drop(wallet);
+        let mut reopened = None;
+        for _ in 0..50 {
+            let response = subscribe(WALLET.to_string()).await;
+            if response.status() == 200 {
+                reopened = Some(response);
+                break;
+            }
+            time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(reopened.is_some());
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_bridge_clients_flow() {
+        use crate::bridge::{AsyncBridgeClient, WalletMessage};
+        use crate::crypto::ClientKeypair;
+        use crate::types::WalletEvent;
+        use futures::StreamExt;
+
+        let (url, _) = start(BridgeServerConfig::default());
+        let app = AsyncBridgeClient::new(&url, ClientKeypair::generate_random()).unwrap();
+        let wallet = AsyncBridgeClient::new(&url, ClientKeypair::generate_random()).unwrap();
+        let mut wallet_messages = app.listen(&None, None).await.unwrap();
+        wallet
+            .send_message(
+                &app.keypair().get_hex_public(),
+                b"{\"event\":\"disconnect\",\"id\":1,\"payload\":{}}",
+                300,
+                &None,
+            )
+            .await
+            .unwrap();
+        let wallet_message = time::timeout(Duration::from_secs(5), wallet_messages.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            wallet_message,
+            WalletMessage::Event(WalletEvent::Disconnect { id: 1 })
+        ));
+    }
+}
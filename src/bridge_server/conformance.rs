@@ -0,0 +1,135 @@
+//! Test suite every `MessageStore` backend must pass.
+//!
+//! Call it from a `#[tokio::test]` of the backend, with a function returning an empty store:
+//!
+//! ```no_run
+//! use ton_connect::bridge_server::{conformance, MemoryMessageStore};
+//!
+//! # async fn run() {
+//! conformance::check_message_store(MemoryMessageStore::new).await;
+//! # }
+//! ```
+
+use super::store::{MessageStore, StoredMessage};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+use tokio::time;
+
+/// Runs every check against fresh stores made by `new_store`, panicking on the first failure.
+pub async fn check_message_store<S, F>(new_store: F)
+where
+    S: MessageStore,
+    F: Fn() -> S,
+{
+    check_read_since(&new_store()).await;
+    check_expire(&new_store()).await;
+    check_subscribe(&new_store()).await;
+}
+
+/// Messages are read by receiver, after the last event id, in id order.
+pub async fn check_read_since<S: MessageStore>(store: &S) {
+    let now = SystemTime::now();
+    let later = now + Duration::from_secs(60);
+    let first = append(store, "wallet", "bWVzc2FnZQ==", later).await;
+    let second = append(store, "other", "b3RoZXI=", later).await;
+    let third = store
+        .append(
+            "app",
+            "wallet",
+            "c2Vjb25k",
+            Some("sendTransaction".to_string()),
+            later,
+        )
+        .await
+        .expect("append failed");
+    assert!(first.id < second.id && second.id < third.id);
+    assert_eq!(third.topic.as_deref(), Some("sendTransaction"));
+
+    let wallet = vec!["wallet".to_string()];
+    assert_eq!(
+        read_since(store, &wallet, None, now).await,
+        vec![first.clone(), third.clone()]
+    );
+    assert_eq!(
+        read_since(store, &wallet, Some(first.id), now).await,
+        vec![third.clone()]
+    );
+    assert!(read_since(store, &wallet, Some(third.id), now)
+        .await
+        .is_empty());
+    let both = vec!["wallet".to_string(), "other".to_string()];
+    assert_eq!(
+        read_since(store, &both, None, now).await,
+        vec![first, second, third]
+    );
+    assert!(read_since(store, &["nobody".to_string()], None, now)
+        .await
+        .is_empty());
+}
+
+/// Expired messages are never read, and are removed by `expire`.
+pub async fn check_expire<S: MessageStore>(store: &S) {
+    let now = SystemTime::now();
+    let later = now + Duration::from_secs(60);
+    let lasting = append(store, "wallet", "bWVzc2FnZQ==", later).await;
+    append(store, "wallet", "c2Vjb25k", now + Duration::from_secs(1)).await;
+
+    let wallet = vec!["wallet".to_string()];
+    assert_eq!(read_since(store, &wallet, None, now).await.len(), 2);
+    let expired_at = now + Duration::from_secs(2);
+    assert_eq!(
+        read_since(store, &wallet, None, expired_at).await,
+        vec![lasting]
+    );
+    store.expire(expired_at).await.expect("expire failed");
+    assert_eq!(read_since(store, &wallet, None, now).await.len(), 1);
+    store.expire(later).await.expect("expire failed");
+    assert!(read_since(store, &wallet, None, now).await.is_empty());
+}
+
+/// Subscribers receive the messages appended after they subscribed, to any client, in id order.
+pub async fn check_subscribe<S: MessageStore>(store: &S) {
+    let later = SystemTime::now() + Duration::from_secs(60);
+    append(store, "wallet", "YmVmb3Jl", later).await;
+    let mut first = store.subscribe().await.expect("subscribe failed");
+    let mut second = store.subscribe().await.expect("subscribe failed");
+    let to_wallet = append(store, "wallet", "bWVzc2FnZQ==", later).await;
+    let to_other = append(store, "other", "b3RoZXI=", later).await;
+
+    for receiver in [&mut first, &mut second] {
+        assert_eq!(receive(receiver).await, to_wallet);
+        assert_eq!(receive(receiver).await, to_other);
+    }
+}
+
+async fn append<S: MessageStore>(
+    store: &S,
+    to: &str,
+    message: &str,
+    expires_at: SystemTime,
+) -> Arc<StoredMessage> {
+    store
+        .append("app", to, message, None, expires_at)
+        .await
+        .expect("append failed")
+}
+
+async fn read_since<S: MessageStore>(
+    store: &S,
+    clients_ids: &[String],
+    last_event_id: Option<u64>,
+    now: SystemTime,
+) -> Vec<Arc<StoredMessage>> {
+    store
+        .read_since(clients_ids, last_event_id, now)
+        .await
+        .expect("read_since failed")
+}
+
+async fn receive(receiver: &mut broadcast::Receiver<Arc<StoredMessage>>) -> Arc<StoredMessage> {
+    time::timeout(Duration::from_secs(5), receiver.recv())
+        .await
+        .expect("no message received")
+        .expect("subscription failed")
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Limits a `BridgeServer` enforces against abuse.
+#[derive(Debug, Clone)]
+pub struct BridgeLimits {
+    /// Largest posted message, in bytes of the base64 body.
+    pub max_message_size: usize,
+    /// Longest TTL of a posted message, in seconds.
+    pub max_ttl: u64,
+    /// Messages posted from one IP address.
+    pub messages_per_ip: RateLimit,
+    /// Messages posted by one client id.
+    pub messages_per_client: RateLimit,
+    /// Events streams open at once from one IP address. A closed stream is released when
+    /// writing its next heartbeat fails.
+    pub subscriptions_per_ip: usize,
+    /// Events streams open at once for one client id.
+    pub subscriptions_per_client: usize,
+    /// Client ids one events stream listens for.
+    pub max_clients_per_subscription: usize,
+}
+
+impl Default for BridgeLimits {
+    fn default() -> Self {
+        Self {
+            max_message_size: 16 * 1024,
+            max_ttl: 300,
+            messages_per_ip: RateLimit::new(600, Duration::from_secs(60)),
+            messages_per_client: RateLimit::new(60, Duration::from_secs(60)),
+            subscriptions_per_ip: 100,
+            subscriptions_per_client: 10,
+            max_clients_per_subscription: 16,
+        }
+    }
+}
+
+/// Allows `requests` in any `period`, refilled steadily.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub period: Duration,
+}
+
+impl RateLimit {
+    pub fn new(requests: u32, period: Duration) -> Self {
+        Self { requests, period }
+    }
+}
+
+/// Counters of the requests a `BridgeServer` rejected, by reason.
+#[derive(Debug, Default)]
+pub struct Rejections {
+    invalid_client_id: AtomicU64,
+    message_too_large: AtomicU64,
+    ttl_too_long: AtomicU64,
+    rate_limited: AtomicU64,
+    too_many_subscriptions: AtomicU64,
+    too_many_clients: AtomicU64,
+}
+
+/// Reason of a rejected request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Rejection {
+    InvalidClientId,
+    MessageTooLarge,
+    TtlTooLong,
+    RateLimited,
+    TooManySubscriptions,
+    TooManyClients,
+}
+
+impl Rejections {
+    /// Requests with a client id that is not 64 hex characters.
+    pub fn invalid_client_id(&self) -> u64 {
+        self.invalid_client_id.load(Ordering::Relaxed)
+    }
+
+    /// Messages over `BridgeLimits::max_message_size`.
+    pub fn message_too_large(&self) -> u64 {
+        self.message_too_large.load(Ordering::Relaxed)
+    }
+
+    /// Messages with a TTL over `BridgeLimits::max_ttl`.
+    pub fn ttl_too_long(&self) -> u64 {
+        self.ttl_too_long.load(Ordering::Relaxed)
+    }
+
+    /// Messages over the per IP or per client id rate limit.
+    pub fn rate_limited(&self) -> u64 {
+        self.rate_limited.load(Ordering::Relaxed)
+    }
+
+    /// Events streams over the per IP or per client id limit.
+    pub fn too_many_subscriptions(&self) -> u64 {
+        self.too_many_subscriptions.load(Ordering::Relaxed)
+    }
+
+    /// Events streams for more than `BridgeLimits::max_clients_per_subscription` client ids.
+    pub fn too_many_clients(&self) -> u64 {
+        self.too_many_clients.load(Ordering::Relaxed)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.invalid_client_id()
+            + self.message_too_large()
+            + self.ttl_too_long()
+            + self.rate_limited()
+            + self.too_many_subscriptions()
+            + self.too_many_clients()
+    }
+
+    pub(super) fn count(&self, rejection: Rejection) {
+        let counter = match rejection {
+            Rejection::InvalidClientId => &self.invalid_client_id,
+            Rejection::MessageTooLarge => &self.message_too_large,
+            Rejection::TtlTooLong => &self.ttl_too_long,
+            Rejection::RateLimited => &self.rate_limited,
+            Rejection::TooManySubscriptions => &self.too_many_subscriptions,
+            Rejection::TooManyClients => &self.too_many_clients,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Token buckets by key, e.g. by IP address.
+#[derive(Debug)]
+pub struct RateLimiter {
+    limit: RateLimit,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: Mutex::default(),
+        }
+    }
+
+    /// Takes a token from the bucket of `key`, returns false when it is empty.
+    pub fn check(&self, key: &str, now: Instant) -> bool {
+        let capacity = f64::from(self.limit.requests);
+        let mut buckets = lock(&self.buckets);
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Forgets the buckets refilled by `now`, which are the same as new ones.
+    pub fn prune(&self, now: Instant) {
+        let capacity = f64::from(self.limit.requests);
+        lock(&self.buckets).retain(|_, bucket| self.refilled(bucket, now) < capacity);
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let capacity = f64::from(self.limit.requests);
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        let refill = elapsed / self.limit.period.as_secs_f64() * capacity;
+        (bucket.tokens + refill).min(capacity)
+    }
+}
+
+/// Counts the events streams open by key, e.g. by client id.
+#[derive(Debug, Clone)]
+pub struct SubscriptionLimiter {
+    max: usize,
+    open: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl SubscriptionLimiter {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            open: Arc::default(),
+        }
+    }
+
+    /// Opens a stream for every key, or none if any key is at the limit.
+    pub fn acquire(&self, keys: &[String]) -> Option<Subscription> {
+        let mut open = lock(&self.open);
+        if keys
+            .iter()
+            .any(|key| open.get(key).copied().unwrap_or_default() >= self.max)
+        {
+            return None;
+        }
+        for key in keys {
+            *open.entry(key.clone()).or_default() += 1;
+        }
+        Some(Subscription {
+            open: self.open.clone(),
+            keys: keys.to_vec(),
+        })
+    }
+}
+
+/// An open events stream, counted until dropped.
+#[derive(Debug)]
+pub struct Subscription {
+    open: Arc<Mutex<HashMap<String, usize>>>,
+    keys: Vec<String>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut open = lock(&self.open);
+        for key in &self.keys {
+            if let Some(count) = open.get_mut(key) {
+                *count -= 1;
+                if *count == 0 {
+                    open.remove(key);
+                }
+            }
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().expect("bridge limits poisoned")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(RateLimit::new(2, Duration::from_secs(10)));
+        let now = Instant::now();
+        assert!(limiter.check("a", now));
+        assert!(limiter.check("a", now));
+        assert!(!limiter.check("a", now));
+        assert!(limiter.check("b", now));
+
+        assert!(!limiter.check("a", now + Duration::from_secs(4)));
+        assert!(limiter.check("a", now + Duration::from_secs(5)));
+
+        limiter.prune(now + Duration::from_secs(10));
+        assert_eq!(lock(&limiter.buckets).len(), 1);
+        limiter.prune(now + Duration::from_secs(20));
+        assert!(lock(&limiter.buckets).is_empty());
+    }
+
+    #[test]
+    fn test_subscription_limiter() {
+        let limiter = SubscriptionLimiter::new(1);
+        let keys = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect::<Vec<_>>();
+        let first = limiter.acquire(&keys(&["a", "b"])).unwrap();
+        assert!(limiter.acquire(&keys(&["b", "c"])).is_none());
+        let second = limiter.acquire(&keys(&["c"])).unwrap();
+        drop(first);
+        assert!(limiter.acquire(&keys(&["b"])).is_some());
+        drop(second);
+        assert!(lock(&limiter.open).is_empty());
+    }
+}
@@ -0,0 +1,168 @@
+use crate::Error;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+/// A message posted to the bridge, kept until it expires.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredMessage {
+    /// Event id, increasing with every message.
+    pub id: u64,
+    /// Client id of the sender.
+    pub from: String,
+    /// Client id of the receiver.
+    pub to: String,
+    /// Encrypted message, base64 encoded, as posted.
+    pub message: String,
+    pub topic: Option<String>,
+    pub expires_at: SystemTime,
+}
+
+/// Future returned by the `MessageStore` methods.
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, Error>> + Send + 'a>>;
+
+/// Keeps the messages posted to the bridge until they expire, and notifies the subscribers.
+///
+/// Bridge servers sharing a store, e.g. one backed by Redis or Postgres, serve the same
+/// clients, so a message posted to any server reaches the events streams of all of them.
+/// Check a backend with `conformance::check_message_store`.
+pub trait MessageStore: Send + Sync {
+    /// Stores a message for its receiver, assigning it an event id greater than any before.
+    fn append<'a>(
+        &'a self,
+        from: &'a str,
+        to: &'a str,
+        message: &'a str,
+        topic: Option<String>,
+        expires_at: SystemTime,
+    ) -> StoreFuture<'a, Arc<StoredMessage>>;
+
+    /// Unexpired messages for any of `clients_ids` with an id after `last_event_id`, in id order.
+    fn read_since<'a>(
+        &'a self,
+        clients_ids: &'a [String],
+        last_event_id: Option<u64>,
+        now: SystemTime,
+    ) -> StoreFuture<'a, Vec<Arc<StoredMessage>>>;
+
+    /// Removes the messages expired at `now`.
+    fn expire(&self, now: SystemTime) -> StoreFuture<'_, ()>;
+
+    /// Receives every message appended from now on, to any client, in id order.
+    ///
+    /// A subscriber falling behind gets `RecvError::Lagged` and catches up with `read_since`.
+    fn subscribe(&self) -> StoreFuture<'_, broadcast::Receiver<Arc<StoredMessage>>>;
+}
+
+/// Keeps the messages in memory, for a single bridge server.
+#[derive(Debug)]
+pub struct MemoryMessageStore {
+    inner: Mutex<Inner>,
+    appended: broadcast::Sender<Arc<StoredMessage>>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    last_id: u64,
+    messages: HashMap<String, VecDeque<Arc<StoredMessage>>>,
+}
+
+impl MemoryMessageStore {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::default(),
+            appended: broadcast::channel(1024).0,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().expect("message store poisoned")
+    }
+}
+
+impl Default for MemoryMessageStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageStore for MemoryMessageStore {
+    /// Ids are microseconds since the epoch when possible, so they keep increasing across restarts.
+    fn append<'a>(
+        &'a self,
+        from: &'a str,
+        to: &'a str,
+        message: &'a str,
+        topic: Option<String>,
+        expires_at: SystemTime,
+    ) -> StoreFuture<'a, Arc<StoredMessage>> {
+        let mut inner = self.lock();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        inner.last_id = now.max(inner.last_id + 1);
+        let stored = Arc::new(StoredMessage {
+            id: inner.last_id,
+            from: from.to_string(),
+            to: to.to_string(),
+            message: message.to_string(),
+            topic,
+            expires_at,
+        });
+        inner
+            .messages
+            .entry(to.to_string())
+            .or_default()
+            .push_back(stored.clone());
+        // Sent under the lock, so the subscribers receive the messages in id order.
+        let _ = self.appended.send(stored.clone());
+        Box::pin(async move { Ok(stored) })
+    }
+
+    fn read_since<'a>(
+        &'a self,
+        clients_ids: &'a [String],
+        last_event_id: Option<u64>,
+        now: SystemTime,
+    ) -> StoreFuture<'a, Vec<Arc<StoredMessage>>> {
+        let inner = self.lock();
+        let mut messages: Vec<_> = clients_ids
+            .iter()
+            .filter_map(|client_id| inner.messages.get(client_id))
+            .flatten()
+            .filter(|message| last_event_id.is_none_or(|last| message.id > last))
+            .filter(|message| message.expires_at > now)
+            .cloned()
+            .collect();
+        messages.sort_by_key(|message| message.id);
+        Box::pin(async move { Ok(messages) })
+    }
+
+    fn expire(&self, now: SystemTime) -> StoreFuture<'_, ()> {
+        self.lock().messages.retain(|_, messages| {
+            messages.retain(|message| message.expires_at > now);
+            !messages.is_empty()
+        });
+        Box::pin(async { Ok(()) })
+    }
+
+    fn subscribe(&self) -> StoreFuture<'_, broadcast::Receiver<Arc<StoredMessage>>> {
+        let receiver = self.appended.subscribe();
+        Box::pin(async move { Ok(receiver) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::conformance::check_message_store;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_message_store() {
+        check_message_store(MemoryMessageStore::new).await;
+    }
+}
@@ -0,0 +1,683 @@
+//! High-level connection to a wallet, the Rust counterpart of `@tonconnect/sdk`.
+//!
+//! `TonConnect` keeps the app keypair, the session and the bridge listener together, so the
+//! app only deals with universal links, requests and status changes.
+
+use crate::bridge::{
+    BlockingBridgeClient, ListenerStatus, ReconnectPolicy, WalletMessage, DEFAULT_TTL,
+};
+use crate::crypto::ClientKeypair;
+use crate::helpers::create_universal_link;
+use crate::session::{
+    RequestError, RequestRegistry, Session, SessionStore, DEFAULT_REQUEST_TIMEOUT,
+};
+use crate::types::{
+    AppRequest, ConnectErrorPayload, ConnectItem, ConnectPayload, ConnectRequest,
+    RawWalletResponse, SendTransactionErrorCode, SendTransactionRequest, SendTransactionResponse,
+    SignDataErrorCode, SignDataRequest, SignDataResponse, WalletEvent,
+};
+use crate::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+/// Where to reach a wallet: the link that opens it and the bridge it listens on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalletConnectionSource {
+    /// Base of the universal link opening the wallet, e.g. `https://app.tonkeeper.com/ton-connect`.
+    pub universal_url: String,
+    /// HTTP bridge of the wallet, e.g. `https://bridge.tonapi.io/bridge`.
+    pub bridge_url: String,
+}
+
+/// Change of the connection with the wallet.
+#[derive(Debug, Clone)]
+pub enum ConnectionStatus {
+    /// The user approved the connection in the wallet.
+    Connected(ConnectPayload),
+    /// The wallet rejected the connection.
+    ConnectError(ConnectErrorPayload),
+    /// The wallet or the app ended the connection.
+    Disconnected,
+    /// The bridge listener gave up reconnecting. The session is kept, `restore_connection`
+    /// listens again from the last event received.
+    ListenerFailed,
+}
+
+/// Connection between the app and a wallet, persisted in a `SessionStore` under a session key.
+///
+/// Uses the blocking bridge client: the listener runs on its own thread and requests block
+/// until the wallet answers.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use ton_connect::connector::{ConnectionStatus, TonConnect, WalletConnectionSource};
+/// use ton_connect::session::FileSessionStore;
+///
+/// let store = Arc::new(FileSessionStore::new("sessions.json"));
+/// let mut connector = TonConnect::new("https://example.com/tonconnect-manifest.json", store, "user-1");
+/// let status = connector.subscribe();
+/// if !connector.restore_connection().unwrap() {
+///     let wallet = WalletConnectionSource {
+///         universal_url: "https://app.tonkeeper.com/ton-connect".to_string(),
+///         bridge_url: "https://bridge.tonapi.io/bridge".to_string(),
+///     };
+///     println!("Open: {}", connector.connect(&wallet, None).unwrap());
+/// }
+/// while let Ok(change) = status.recv() {
+///     if let ConnectionStatus::Connected(payload) = change {
+///         println!("Connected to {:?}", payload.device);
+///         break;
+///     }
+/// }
+/// ```
+pub struct TonConnect {
+    manifest_url: String,
+    shared: Arc<Shared>,
+    reconnect_policy: ReconnectPolicy,
+    request_timeout: Duration,
+    client: Option<BlockingBridgeClient>,
+    registry: RequestRegistry,
+    listener_stopped: Option<Arc<AtomicBool>>,
+}
+
+/// State shared with the listener thread.
+struct Shared {
+    session_key: String,
+    store: Arc<dyn SessionStore + Send + Sync>,
+    session: Mutex<Option<Session>>,
+    subscribers: Mutex<Vec<Sender<ConnectionStatus>>>,
+}
+
+impl TonConnect {
+    /// Creates a connector for the app described by the manifest at `manifest_url`, keeping its
+    /// session in `store` under `session_key`.
+    pub fn new(
+        manifest_url: &str,
+        store: Arc<dyn SessionStore + Send + Sync>,
+        session_key: &str,
+    ) -> Self {
+        Self {
+            manifest_url: manifest_url.to_string(),
+            shared: Arc::new(Shared {
+                session_key: session_key.to_string(),
+                store,
+                session: Mutex::new(None),
+                subscribers: Mutex::new(Vec::new()),
+            }),
+            reconnect_policy: ReconnectPolicy::default(),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            client: None,
+            registry: RequestRegistry::new(DEFAULT_REQUEST_TIMEOUT),
+            listener_stopped: None,
+        }
+    }
+
+    /// Sets how the bridge listener reconnects.
+    pub fn with_reconnect_policy(mut self, reconnect_policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = reconnect_policy;
+        self
+    }
+
+    /// Sets how long requests wait for the wallet to answer.
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Returns a channel receiving every following change of the connection.
+    pub fn subscribe(&self) -> Receiver<ConnectionStatus> {
+        let (sender, receiver) = channel();
+        lock(&self.shared.subscribers).push(sender);
+        receiver
+    }
+
+    /// Returns a copy of the current session, if any.
+    pub fn session(&self) -> Option<Session> {
+        lock(&self.shared.session).clone()
+    }
+
+    /// Whether a wallet is connected.
+    pub fn is_connected(&self) -> bool {
+        lock(&self.shared.session)
+            .as_ref()
+            .is_some_and(Session::is_connected)
+    }
+
+    /// Payload of the connect event of the connected wallet, with its address and device.
+    pub fn wallet(&self) -> Option<ConnectPayload> {
+        lock(&self.shared.session)
+            .as_ref()
+            .and_then(|session| session.connect_payload.clone())
+    }
+
+    /// Starts a new connection with `wallet` and returns the universal link to open it with.
+    ///
+    /// Requests the wallet address, and a `ton_proof` of it when `ton_proof_payload` is given.
+    /// Replaces a pending connection, the result comes as a `ConnectionStatus`.
+    pub fn connect(
+        &mut self,
+        wallet: &WalletConnectionSource,
+        ton_proof_payload: Option<String>,
+    ) -> Result<String, Error> {
+        if self.is_connected() {
+            return Err(Error::AlreadyConnected);
+        }
+        let keypair = ClientKeypair::generate_random();
+        let mut items = vec![ConnectItem::TonAddressItem];
+        if let Some(payload) = ton_proof_payload {
+            items.push(ConnectItem::TonProofItem { payload });
+        }
+        let connect_request = ConnectRequest {
+            manifest_url: self.manifest_url.clone(),
+            items,
+        };
+        let universal_link = create_universal_link(
+            &wallet.universal_url,
+            &keypair.get_hex_public(),
+            &connect_request,
+        )?;
+
+        let session = Session::new(&keypair, &wallet.bridge_url);
+        self.shared.store.save(&self.shared.session_key, &session)?;
+        self.start(session)?;
+        Ok(universal_link)
+    }
+
+    /// Resumes the session kept in the store, listening from the last event received.
+    ///
+    /// Returns whether a wallet is connected, a pending connection is resumed as well.
+    pub fn restore_connection(&mut self) -> Result<bool, Error> {
+        match self.shared.store.load(&self.shared.session_key)? {
+            Some(session) => {
+                let connected = session.is_connected();
+                self.start(session)?;
+                Ok(connected)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Asks the wallet to sign and send a transaction, and waits for the answer.
+    pub fn send_transaction(
+        &self,
+        params: SendTransactionRequest,
+    ) -> Result<SendTransactionResponse, Error> {
+        let response = self.send_request(|id| AppRequest::SendTransaction { id, params })?;
+        Ok(response.parse::<String, SendTransactionErrorCode>()?)
+    }
+
+    /// Asks the wallet to sign data, and waits for the answer.
+    pub fn sign_data(&self, params: SignDataRequest) -> Result<SignDataResponse, Error> {
+        let response = self.send_request(|id| AppRequest::SignData { id, params })?;
+        Ok(response.parse::<_, SignDataErrorCode>()?)
+    }
+
+    /// Tells the wallet the app disconnects, then forgets the session.
+    ///
+    /// Does not wait for the wallet to answer.
+    pub fn disconnect(&mut self) -> Result<(), Error> {
+        if self.is_connected() {
+            let id = self.registry.register().id().to_string();
+            self.shared.save_request_id(self.registry.next_id())?;
+            self.post(&AppRequest::Disconnect { id })?;
+        }
+        self.stop();
+        self.registry.fail_all(RequestError::Disconnected);
+        self.shared
+            .end(lock(&self.shared.session), ConnectionStatus::Disconnected)?;
+        Ok(())
+    }
+
+    fn send_request<F>(&self, request: F) -> Result<RawWalletResponse, Error>
+    where
+        F: FnOnce(String) -> AppRequest,
+    {
+        if !self.is_connected() {
+            return Err(Error::NotConnected);
+        }
+        let pending = self.registry.register();
+        self.shared.save_request_id(self.registry.next_id())?;
+        self.post(&request(pending.id().to_string()))?;
+        Ok(pending.wait()?)
+    }
+
+    fn post(&self, request: &AppRequest) -> Result<(), Error> {
+        let client = self.client.as_ref().ok_or(Error::NotConnected)?;
+        let wallet_public_key = lock(&self.shared.session)
+            .as_ref()
+            .and_then(|session| session.wallet_public_key.clone())
+            .ok_or(Error::NotConnected)?;
+        let message = serde_json::to_vec(request)?;
+        client.send_message(
+            &wallet_public_key,
+            &message,
+            DEFAULT_TTL,
+            &Some(request.topic()),
+        )?;
+        Ok(())
+    }
+
+    /// Starts listening for the wallet messages of `session`, replacing the current listener.
+    fn start(&mut self, session: Session) -> Result<(), Error> {
+        self.stop();
+        let client = BlockingBridgeClient::new(&session.bridge_url, session.keypair()?)?
+            .with_reconnect_policy(self.reconnect_policy.clone());
+        let registry = session.request_registry(self.request_timeout);
+        let stopped = Arc::new(AtomicBool::new(false));
+        let wallet_messages = client
+            .listen(&None, session.last_event_id.clone())?
+            .with_stop_flag(stopped.clone());
+        *lock(&self.shared.session) = Some(session);
+
+        let shared = self.shared.clone();
+        let listener_registry = registry.clone();
+        let listener_stopped = stopped.clone();
+        thread::spawn(move || {
+            let mut wallet_messages = wallet_messages;
+            while let Some(wallet_message) = wallet_messages.next() {
+                let wallet_message = match wallet_message {
+                    Ok(wallet_message) => wallet_message,
+                    // The listener gave up reconnecting.
+                    Err(_) if wallet_messages.state().status == ListenerStatus::Closed => break,
+                    // Messages that cannot be decrypted or parsed are skipped.
+                    Err(_) => continue,
+                };
+                let state = wallet_messages.state();
+                let handled = shared.handle(
+                    wallet_message,
+                    state.last_sender.clone(),
+                    state.last_event_id.clone(),
+                    &listener_registry,
+                    &listener_stopped,
+                );
+                if !handled.unwrap_or(true) {
+                    listener_stopped.store(true, Ordering::SeqCst);
+                    break;
+                }
+            }
+            if !listener_stopped.load(Ordering::SeqCst) {
+                listener_registry.fail_all(RequestError::Disconnected);
+                shared.notify(ConnectionStatus::ListenerFailed);
+            }
+        });
+
+        self.client = Some(client);
+        self.registry = registry;
+        self.listener_stopped = Some(stopped);
+        Ok(())
+    }
+
+    /// Stops the current listener. Its thread ends after the current read, at the latest on the
+    /// next heartbeat or the heartbeat timeout.
+    fn stop(&mut self) {
+        if let Some(stopped) = self.listener_stopped.take() {
+            stopped.store(true, Ordering::SeqCst);
+        }
+        self.client = None;
+    }
+}
+
+impl Drop for TonConnect {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Shared {
+    /// Applies a wallet message to the session. Returns whether to keep listening.
+    ///
+    /// The message is dropped when `stopped` is set, the session then belongs to another listener.
+    fn handle(
+        &self,
+        wallet_message: WalletMessage,
+        sender: Option<String>,
+        last_event_id: Option<String>,
+        registry: &RequestRegistry,
+        stopped: &AtomicBool,
+    ) -> Result<bool, Error> {
+        let mut session = lock(&self.session);
+        if stopped.load(Ordering::SeqCst) {
+            return Ok(false);
+        }
+        let current = match session.as_mut() {
+            Some(current) => current,
+            None => return Ok(false),
+        };
+        // Once connected, only the connected wallet is listened to.
+        if current.wallet_public_key.is_some() && current.wallet_public_key != sender {
+            return Ok(true);
+        }
+        current.last_event_id = last_event_id;
+
+        match wallet_message {
+            WalletMessage::Event(WalletEvent::Connect { payload, .. }) => {
+                current.wallet_public_key = sender;
+                current.connect_payload = Some(payload.clone());
+                self.store.save(&self.session_key, current)?;
+                drop(session);
+                self.notify(ConnectionStatus::Connected(payload));
+                Ok(true)
+            }
+            WalletMessage::Event(WalletEvent::ConnectError { payload, .. }) => {
+                self.end(session, ConnectionStatus::ConnectError(payload))?;
+                Ok(false)
+            }
+            WalletMessage::Event(event @ WalletEvent::Disconnect { .. }) => {
+                registry.handle_event(&event);
+                self.end(session, ConnectionStatus::Disconnected)?;
+                Ok(false)
+            }
+            WalletMessage::Response(response) => {
+                self.store.save(&self.session_key, current)?;
+                drop(session);
+                registry.resolve(response);
+                Ok(true)
+            }
+        }
+    }
+
+    fn save_request_id(&self, next_request_id: u64) -> Result<(), Error> {
+        let mut session = lock(&self.session);
+        if let Some(session) = session.as_mut() {
+            session.next_request_id = next_request_id;
+            self.store.save(&self.session_key, session)?;
+        }
+        Ok(())
+    }
+
+    /// Forgets the session and reports how the connection ended.
+    ///
+    /// Takes the session lock so that no other listener replaces the session in between.
+    fn end(
+        &self,
+        mut session: MutexGuard<'_, Option<Session>>,
+        status: ConnectionStatus,
+    ) -> Result<(), Error> {
+        let had_session = session.take().is_some();
+        self.store.delete(&self.session_key)?;
+        drop(session);
+        if had_session {
+            self.notify(status);
+        }
+        Ok(())
+    }
+
+    fn notify(&self, status: ConnectionStatus) {
+        lock(&self.subscribers).retain(|subscriber| subscriber.send(status.clone()).is_ok());
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().expect("connector state poisoned")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::test_bridge::{
+        message_event_to, write_events, write_events_head, write_status, StandInBridge,
+    };
+    use crate::crypto::open_from;
+    use crate::session::MemorySessionStore;
+    use crate::types::{BridgeMessage, WalletResponse};
+    use std::net::TcpStream;
+
+    const CONNECT_EVENT: &str = "{\"id\":1,\"event\":\"connect\",\"payload\":{\"items\":[{\"name\":\"ton_addr\",\"address\":\"0:dc69be3a989b1513f14e9e4ecc46ff6753770a2b199138708ff66238c2719e99\",\"network\":\"-239\",\"publicKey\":\"321b717a3a455096b6c3d20ad3f66207125557dacbb888fca9e00023aa89bfff\",\"walletStateInit\":\"te6cckEBAQEAAgAAAEysuc0=\"}],\"device\":{\"platform\":\"iphone\",\"appName\":\"Tonkeeper\",\"appVersion\":\"3.0.304\",\"maxProtocolVersion\":2,\"features\":[\"SendTransaction\"]}}}";
+
+    /// Query parameter of a request target.
+    fn query_param(target: &str, name: &str) -> Option<String> {
+        let url = url::Url::parse(&format!("http://localhost{}", target)).unwrap();
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    /// Decrypts an app request posted to the stand-in bridge.
+    fn decrypt_request(wallet: &ClientKeypair, app_public: &str, body: &str) -> serde_json::Value {
+        let bridge_message = BridgeMessage {
+            from: app_public.to_string(),
+            message: body.to_string(),
+        };
+        serde_json::from_slice(&open_from(wallet, &bridge_message).unwrap()).unwrap()
+    }
+
+    /// Stand-in bridge playing the wallet: connects on the first listen and answers every
+    /// sendTransaction with a signed BOC.
+    fn wallet_bridge(wallet: ClientKeypair, connect: bool) -> StandInBridge {
+        let events: Arc<Mutex<Option<TcpStream>>> = Arc::new(Mutex::new(None));
+        let mut event_id = 0;
+        StandInBridge::start(move |request, stream| {
+            event_id += 1;
+            if request.method == "GET" {
+                let app_public = query_param(&request.target, "client_id").unwrap();
+                let connect_event = if connect {
+                    message_event_to(&wallet, &app_public, event_id, CONNECT_EVENT)
+                } else {
+                    String::new()
+                };
+                write_events_head(stream, &connect_event);
+                *events.lock().unwrap() = Some(stream.try_clone().unwrap());
+                return;
+            }
+
+            write_status(stream, "200 OK");
+            let app_public = query_param(&request.target, "client_id").unwrap();
+            let app_request = decrypt_request(&wallet, &app_public, &request.body);
+            if app_request["method"] == "sendTransaction" {
+                let response = format!(
+                    "{{\"result\":\"te6cc\",\"id\":\"{}\"}}",
+                    app_request["id"].as_str().unwrap()
+                );
+                let event = message_event_to(&wallet, &app_public, event_id, &response);
+                if let Some(events) = events.lock().unwrap().as_mut() {
+                    let _ = std::io::Write::write_all(events, event.as_bytes());
+                }
+            }
+        })
+    }
+
+    fn transaction() -> SendTransactionRequest {
+        SendTransactionRequest {
+            valid_until: 1658253458,
+            network: None,
+            from: None,
+            messages: vec![],
+        }
+    }
+
+    #[test]
+    fn test_connect_send_disconnect() {
+        let wallet = ClientKeypair::generate_random();
+        let wallet_public = wallet.get_hex_public();
+        let bridge = wallet_bridge(wallet, true);
+        let store: Arc<MemorySessionStore> = Arc::new(MemorySessionStore::new());
+        let mut connector =
+            TonConnect::new("https://example.com/manifest.json", store.clone(), "user");
+        let status = connector.subscribe();
+
+        let source = WalletConnectionSource {
+            universal_url: "https://app.tonkeeper.com/ton-connect".to_string(),
+            bridge_url: bridge.url.clone(),
+        };
+        let link = connector
+            .connect(&source, Some("halo".to_string()))
+            .unwrap();
+        let app_public = connector
+            .session()
+            .unwrap()
+            .keypair()
+            .unwrap()
+            .get_hex_public();
+        assert_eq!(
+            query_param(&link[link.find('?').unwrap()..], "id"),
+            Some(app_public)
+        );
+        assert!(matches!(
+            status.recv().unwrap(),
+            ConnectionStatus::Connected(_)
+        ));
+        assert!(connector.is_connected());
+        assert!(connector.connect(&source, None).is_err());
+
+        let stored = store.load("user").unwrap().unwrap();
+        assert_eq!(stored.wallet_public_key, Some(wallet_public));
+        assert_eq!(stored.last_event_id.as_deref(), Some("1"));
+
+        let response = connector.send_transaction(transaction()).unwrap();
+        assert!(
+            matches!(response, WalletResponse::Success { ref result, .. } if result == "te6cc")
+        );
+        assert_eq!(store.load("user").unwrap().unwrap().next_request_id, 1);
+
+        connector.disconnect().unwrap();
+        assert!(matches!(
+            status.recv().unwrap(),
+            ConnectionStatus::Disconnected
+        ));
+        assert!(store.load("user").unwrap().is_none());
+        assert!(connector.send_transaction(transaction()).is_err());
+
+        let targets: Vec<String> = bridge
+            .requests
+            .try_iter()
+            .map(|request| request.target)
+            .collect();
+        assert!(targets
+            .iter()
+            .any(|target| target.contains("topic=disconnect")));
+    }
+
+    #[test]
+    fn test_restore_connection() {
+        let wallet = ClientKeypair::generate_random();
+        let bridge = wallet_bridge(
+            ClientKeypair::from_hex_secret(wallet.get_hex_secret().expose_secret()).unwrap(),
+            false,
+        );
+        let store: Arc<MemorySessionStore> = Arc::new(MemorySessionStore::new());
+
+        let mut session = Session::new(&ClientKeypair::generate_random(), &bridge.url);
+        session.wallet_public_key = Some(wallet.get_hex_public());
+        session.connect_payload = match serde_json::from_str(CONNECT_EVENT).unwrap() {
+            WalletEvent::Connect { payload, .. } => Some(payload),
+            _ => unreachable!(),
+        };
+        session.last_event_id = Some("41".to_string());
+        session.next_request_id = 5;
+        store.save("user", &session).unwrap();
+
+        let mut connector =
+            TonConnect::new("https://example.com/manifest.json", store.clone(), "user");
+        assert!(connector.restore_connection().unwrap());
+        let listen = bridge.requests.recv().unwrap();
+        assert_eq!(
+            query_param(&listen.target, "last_event_id").as_deref(),
+            Some("41")
+        );
+
+        assert!(connector.send_transaction(transaction()).is_ok());
+        let post = bridge.requests.recv().unwrap();
+        let app_public = session.keypair().unwrap().get_hex_public();
+        assert_eq!(decrypt_request(&wallet, &app_public, &post.body)["id"], "5");
+
+        let mut other = TonConnect::new("https://example.com/manifest.json", store, "other");
+        assert!(!other.restore_connection().unwrap());
+    }
+
+    #[test]
+    fn test_listener_ends_when_dropped() {
+        // Every events stream closes at once, so the listener reconnects until it is stopped.
+        let bridge = StandInBridge::start(|_, stream| write_events(stream, ""));
+        let store: Arc<MemorySessionStore> = Arc::new(MemorySessionStore::new());
+        let session = Session::new(&ClientKeypair::generate_random(), &bridge.url);
+        store.save("user", &session).unwrap();
+
+        let mut connector = TonConnect::new("https://example.com/manifest.json", store, "user")
+            .with_reconnect_policy(ReconnectPolicy {
+                initial_delay: Duration::from_millis(10),
+                max_delay: Duration::from_millis(10),
+                max_attempts: None,
+                heartbeat_timeout: Duration::from_millis(300),
+            });
+        connector.restore_connection().unwrap();
+        bridge.requests.recv().unwrap();
+        bridge.requests.recv().unwrap();
+
+        drop(connector);
+        // A reconnection already underway may still reach the bridge, none after it.
+        thread::sleep(Duration::from_millis(100));
+        while bridge.requests.try_recv().is_ok() {}
+        assert!(bridge
+            .requests
+            .recv_timeout(Duration::from_millis(500))
+            .is_err());
+    }
+
+    #[test]
+    fn test_listener_failed() {
+        // The first events stream closes at once, every reconnection is refused.
+        let mut listens = 0;
+        let bridge = StandInBridge::start(move |_, stream| {
+            listens += 1;
+            if listens == 1 {
+                write_events(stream, "");
+            } else {
+                write_status(stream, "503 Service Unavailable");
+            }
+        });
+        let store: Arc<MemorySessionStore> = Arc::new(MemorySessionStore::new());
+        let session = Session::new(&ClientKeypair::generate_random(), &bridge.url);
+        store.save("user", &session).unwrap();
+
+        let mut connector =
+            TonConnect::new("https://example.com/manifest.json", store.clone(), "user")
+                .with_reconnect_policy(ReconnectPolicy {
+                    initial_delay: Duration::from_millis(10),
+                    max_delay: Duration::from_millis(10),
+                    max_attempts: Some(2),
+                    heartbeat_timeout: Duration::from_millis(300),
+                });
+        let status = connector.subscribe();
+        connector.restore_connection().unwrap();
+        assert!(matches!(
+            status.recv_timeout(Duration::from_secs(5)).unwrap(),
+            ConnectionStatus::ListenerFailed
+        ));
+        assert!(store.load("user").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_replaced_listener_messages_dropped() {
+        let store: Arc<MemorySessionStore> = Arc::new(MemorySessionStore::new());
+        let session = Session::new(&ClientKeypair::generate_random(), "http://localhost/bridge");
+        store.save("user", &session).unwrap();
+        let shared = Shared {
+            session_key: "user".to_string(),
+            store: store.clone(),
+            session: Mutex::new(Some(session)),
+            subscribers: Mutex::new(Vec::new()),
+        };
+        let connect_error: WalletEvent = serde_json::from_str(
+            "{\"event\":\"connect_error\",\"id\":1,\"payload\":{\"code\":300,\"message\":\"User declined\"}}",
+        )
+        .unwrap();
+
+        let stopped = AtomicBool::new(true);
+        let handled = shared.handle(
+            WalletMessage::Event(connect_error),
+            Some(ClientKeypair::generate_random().get_hex_public()),
+            Some("7".to_string()),
+            &RequestRegistry::new(DEFAULT_REQUEST_TIMEOUT),
+            &stopped,
+        );
+        assert!(!handled.unwrap());
+        let kept = store.load("user").unwrap().unwrap();
+        assert_eq!(kept.last_event_id, None);
+        assert_eq!(lock(&shared.session).as_ref().unwrap().last_event_id, None);
+    }
+}
@@ -1,15 +1,35 @@
+use crate::types::BridgeMessage;
+use crate::Error;
+use base64::{engine::general_purpose, Engine as _};
 use crypto_box::{
     aead::{
         generic_array::{typenum::U24, GenericArray},
-        Aead, OsRng,
+        Aead, AeadInPlace, OsRng,
     },
     PublicKey, SalsaBox, SecretKey,
 };
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 use std::convert::TryInto;
+use std::fmt;
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, Zeroizing};
+
+pub use crypto_box::aead::Buffer;
 
 /// The length of the nonce used for encryption and decryption operations.
 pub const NONCE_LENGTH: usize = 24;
 
+/// The length of the authentication tag prepended to the ciphertext.
+const TAG_LENGTH: usize = 16;
+
+/// Minimum length of the master seed of `ClientKeypair::derive`.
+pub const MIN_MASTER_SEED_LENGTH: usize = 32;
+
+/// HKDF salt of `ClientKeypair::derive`, separating its keys from other uses of the master seed.
+const DERIVE_SALT: &[u8] = b"ton-connect/client-keypair";
+
 /// Generates a random nonce of length `NONCE_LENGTH`.
 ///
 /// # Example
@@ -40,7 +60,7 @@ pub fn generate_random_nonce() -> [u8; NONCE_LENGTH] {
 /// let nonce = [0u8; NONCE_LENGTH];
 /// let plaintext = b"Hello, World!";
 /// let ciphertext = encrypt_message(
-///     &sender_keypair.secret,
+///     sender_keypair.secret(),
 ///     plaintext,
 ///     &nonce,
 ///     &receiver_public,
@@ -52,17 +72,21 @@ pub fn encrypt_message(
     plaintext: &[u8],
     nonce: &[u8],
     receiver_pubkey: &str,
-) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let pubkey = parse_hex_pubkey(receiver_pubkey)?;
-    let my_box: SalsaBox = create_box(&secret, &pubkey);
-    if nonce.len() != NONCE_LENGTH {
-        return Err("incorrect nonce size".into());
-    }
-    let nonce = GenericArray::<u8, U24>::from_slice(&nonce);
-    let ciphertext = my_box
-        .encrypt(nonce, plaintext)
-        .map_err(|_| "encryption failed")?;
-    Ok(ciphertext)
+) -> Result<Vec<u8>, Error> {
+    PeerChannel::with_secret(secret, parse_hex_pubkey(receiver_pubkey)?).encrypt(plaintext, nonce)
+}
+
+/// Encrypts the plaintext held by `buffer` in place, leaving the ciphertext in it.
+///
+/// The ciphertext is 16 bytes longer than the plaintext, reserve them to avoid a reallocation.
+pub fn encrypt_in_place<B: Buffer>(
+    secret: &SecretKey,
+    buffer: &mut B,
+    nonce: &[u8],
+    receiver_pubkey: &str,
+) -> Result<(), Error> {
+    PeerChannel::with_secret(secret, parse_hex_pubkey(receiver_pubkey)?)
+        .encrypt_in_place(buffer, nonce)
 }
 
 /// Decrypts a ciphertext message using the key pair and the sender's public key.
@@ -81,13 +105,13 @@ pub fn encrypt_message(
 /// let nonce = [0u8; NONCE_LENGTH];
 /// let plaintext = b"Hello, World!";
 /// let ciphertext = encrypt_message(
-///     &sender_keypair.secret,
+///     sender_keypair.secret(),
 ///     plaintext,
 ///     &nonce,
 ///     &receiver_keypair.get_hex_public()
 /// ).unwrap();
 /// let decrypted = decrypt_message(
-///     &receiver_keypair.secret,
+///     receiver_keypair.secret(),
 ///     &ciphertext,
 ///     &nonce,
 ///     &sender_keypair.get_hex_public()
@@ -99,23 +123,176 @@ pub fn decrypt_message(
     ciphertext: &[u8],
     nonce: &[u8],
     sender_pubkey: &str,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let pubkey = parse_hex_pubkey(sender_pubkey)?;
-    let my_box: SalsaBox = create_box(&secret, &pubkey);
-    if nonce.len() != NONCE_LENGTH {
-        return Err("incorrect nonce size".into());
+) -> Result<String, Error> {
+    let plaintext = decrypt_message_bytes(secret, ciphertext, nonce, sender_pubkey)?;
+    Ok(String::from_utf8(plaintext)?)
+}
+
+/// Decrypts a ciphertext message into bytes, for binary payloads or to parse JSON without a UTF-8 pass.
+///
+/// # Example
+///
+/// ```
+/// use ton_connect::crypto::{decrypt_message_bytes, encrypt_message, ClientKeypair, NONCE_LENGTH};
+///
+/// let sender_keypair = ClientKeypair::generate_random();
+/// let receiver_keypair = ClientKeypair::generate_random();
+/// let nonce = [0u8; NONCE_LENGTH];
+/// let ciphertext = encrypt_message(
+///     sender_keypair.secret(),
+///     &[0xff, 0x00],
+///     &nonce,
+///     &receiver_keypair.get_hex_public(),
+/// )
+/// .unwrap();
+/// let decrypted = decrypt_message_bytes(
+///     receiver_keypair.secret(),
+///     &ciphertext,
+///     &nonce,
+///     &sender_keypair.get_hex_public(),
+/// )
+/// .unwrap();
+/// assert_eq!(decrypted, vec![0xff, 0x00]);
+/// ```
+pub fn decrypt_message_bytes(
+    secret: &SecretKey,
+    ciphertext: &[u8],
+    nonce: &[u8],
+    sender_pubkey: &str,
+) -> Result<Vec<u8>, Error> {
+    let mut buffer = ciphertext.to_vec();
+    decrypt_in_place(secret, &mut buffer, nonce, sender_pubkey)?;
+    Ok(buffer)
+}
+
+/// Decrypts the ciphertext held by `buffer` in place, leaving the plaintext in it.
+///
+/// On error the content of `buffer` is unspecified.
+pub fn decrypt_in_place<B: Buffer>(
+    secret: &SecretKey,
+    buffer: &mut B,
+    nonce: &[u8],
+    sender_pubkey: &str,
+) -> Result<(), Error> {
+    PeerChannel::with_secret(secret, parse_hex_pubkey(sender_pubkey)?)
+        .decrypt_in_place(buffer, nonce)
+}
+
+/// Encrypts `message` for the `receiver_pubkey` client ID into the envelope of the bridge protocol:
+/// base64 of a random nonce followed by the ciphertext.
+///
+/// # Example
+///
+/// ```
+/// use ton_connect::crypto::{open_from, seal_for, ClientKeypair};
+/// use ton_connect::types::BridgeMessage;
+///
+/// let app = ClientKeypair::generate_random();
+/// let wallet = ClientKeypair::generate_random();
+/// let message = seal_for(&app, &wallet.get_hex_public(), b"{\"method\":\"disconnect\"}").unwrap();
+///
+/// let bridge_message = BridgeMessage {
+///     from: app.get_hex_public(),
+///     message,
+/// };
+/// assert_eq!(open_from(&wallet, &bridge_message).unwrap(), b"{\"method\":\"disconnect\"}");
+/// ```
+pub fn seal_for(
+    keypair: &ClientKeypair,
+    receiver_pubkey: &str,
+    message: &[u8],
+) -> Result<String, Error> {
+    PeerChannel::from_hex(keypair, receiver_pubkey)?.seal(message)
+}
+
+/// Decrypts the envelope of a message received from the bridge, sent to `keypair` by `bridge_message.from`.
+pub fn open_from(
+    keypair: &ClientKeypair,
+    bridge_message: &BridgeMessage,
+) -> Result<Vec<u8>, Error> {
+    let mut envelope = general_purpose::STANDARD.decode(&bridge_message.message)?;
+    open_in_place(keypair, &bridge_message.from, &mut envelope)?;
+    Ok(envelope)
+}
+
+/// Decrypts in place an envelope already decoded from base64, sent to `keypair` by the `sender_pubkey` client ID.
+///
+/// The nonce is removed and the ciphertext replaced with the plaintext, without allocating.
+pub fn open_in_place(
+    keypair: &ClientKeypair,
+    sender_pubkey: &str,
+    envelope: &mut Vec<u8>,
+) -> Result<(), Error> {
+    PeerChannel::from_hex(keypair, sender_pubkey)?.open_in_place(envelope)
+}
+
+/// Compares two byte strings in constant time, e.g. keys or secrets received from a peer.
+///
+/// Only the lengths may leak through timing.
+///
+/// # Example
+///
+/// ```
+/// use ton_connect::crypto::constant_time_eq;
+///
+/// assert!(constant_time_eq(b"secret", b"secret"));
+/// assert!(!constant_time_eq(b"secret", b"secreT"));
+/// ```
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+/// A string holding secret material, such as a hex secret key.
+///
+/// Zeroized on drop, redacted in `Debug` and compared in constant time. Serialized as a
+/// plain string so it can be persisted, e.g. in a `Session`.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(secret: String) -> Self {
+        Self(secret)
+    }
+
+    /// Returns the secret. Avoid copying it into values that are not zeroized.
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        Self::new(secret)
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString(...)")
     }
-    let nonce = GenericArray::<u8, U24>::from_slice(&nonce);
-    let plaintext = my_box
-        .decrypt(nonce, ciphertext)
-        .map_err(|_| "decryption failed")
-        .map(String::from_utf8)??;
-    Ok(plaintext)
 }
 
+impl PartialEq for SecretString {
+    fn eq(&self, other: &Self) -> bool {
+        constant_time_eq(self.0.as_bytes(), other.0.as_bytes())
+    }
+}
+
+impl Eq for SecretString {}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// X25519 key pair identifying a client on the bridge. Its public key is the client ID.
+///
+/// The secret key is zeroized on drop and redacted in `Debug`.
 pub struct ClientKeypair {
     pub public: PublicKey,
-    pub secret: SecretKey,
+    secret: SecretKey,
 }
 
 impl ClientKeypair {
@@ -127,8 +304,7 @@ impl ClientKeypair {
     /// use ton_connect::crypto::ClientKeypair;
     ///
     /// let keypair = ClientKeypair::generate_random();
-    /// println!("Public Key: {:?}", keypair.public);
-    /// println!("Secret Key: {:?}", keypair.secret);
+    /// println!("Public Key: {}", keypair.get_hex_public());
     /// ```
     pub fn generate_random() -> Self {
         let secret = SecretKey::generate(&mut OsRng);
@@ -146,11 +322,44 @@ impl ClientKeypair {
     ///
     /// let hex_secret = "e9f5b8703c101f2e8abd79789e8996a119d6aa4730eab85751e9ab03861c48a3";
     /// let keypair = ClientKeypair::from_hex_secret(hex_secret).unwrap();
-    /// println!("Public Key: {:?}", keypair.public);
-    /// println!("Secret Key: {:?}", keypair.secret);
+    /// assert_eq!(
+    ///     keypair.get_hex_public(),
+    ///     "23abbf5a0e9b037d47b0a345cee856962b7cdb2022f7edb0b4b613434f022128"
+    /// );
     /// ```
-    pub fn from_hex_secret(hex_secret: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let secret = parse_hex_secret(&hex_secret)?;
+    pub fn from_hex_secret(hex_secret: &str) -> Result<Self, Error> {
+        let secret = parse_hex_secret(hex_secret)?;
+        let public = secret.public_key();
+
+        Ok(Self { secret, public })
+    }
+
+    /// Derives a `ClientKeypair` from a master seed and a context, e.g. a user id.
+    ///
+    /// The secret key is HKDF-SHA256 of the seed, with the `ton-connect/client-keypair` salt and
+    /// the context as info. The same seed and context always give the same keypair, so sessions
+    /// can be restored without storing their secret. Fails if the seed is shorter than
+    /// `MIN_MASTER_SEED_LENGTH` bytes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ton_connect::crypto::ClientKeypair;
+    ///
+    /// let master_seed = [7u8; 32];
+    /// let keypair = ClientKeypair::derive(&master_seed, b"user-1").unwrap();
+    /// assert_eq!(keypair, ClientKeypair::derive(&master_seed, b"user-1").unwrap());
+    /// assert_ne!(keypair, ClientKeypair::derive(&master_seed, b"user-2").unwrap());
+    /// ```
+    pub fn derive(master_seed: &[u8], context: &[u8]) -> Result<Self, Error> {
+        if master_seed.len() < MIN_MASTER_SEED_LENGTH {
+            return Err(Error::SeedLength(master_seed.len()));
+        }
+        let mut bytes = Zeroizing::new([0u8; 32]);
+        Hkdf::<Sha256>::new(Some(DERIVE_SALT), master_seed)
+            .expand(context, bytes.as_mut())
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        let secret = SecretKey::from(*bytes);
         let public = secret.public_key();
 
         Ok(Self { secret, public })
@@ -165,10 +374,16 @@ impl ClientKeypair {
     ///
     /// let keypair = ClientKeypair::generate_random();
     /// let hex_secret = keypair.get_hex_secret();
-    /// println!("Hex secret: {}", hex_secret);
+    /// let restored = ClientKeypair::from_hex_secret(hex_secret.expose_secret()).unwrap();
+    /// assert_eq!(restored, keypair);
     /// ```
-    pub fn get_hex_secret(&self) -> String {
-        hex::encode(self.secret.as_bytes())
+    pub fn get_hex_secret(&self) -> SecretString {
+        SecretString::new(hex::encode(self.secret.as_bytes()))
+    }
+
+    /// Returns the secret key, to use with the encryption functions.
+    pub fn secret(&self) -> &SecretKey {
+        &self.secret
     }
 
     /// Retrieves the hexadecimal representation of the public key.
@@ -187,6 +402,172 @@ impl ClientKeypair {
     }
 }
 
+impl fmt::Debug for ClientKeypair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientKeypair")
+            .field("public", &self.get_hex_public())
+            .field("secret", &"...")
+            .finish()
+    }
+}
+
+/// Compares the secret keys in constant time.
+impl PartialEq for ClientKeypair {
+    fn eq(&self, other: &Self) -> bool {
+        constant_time_eq(self.secret.as_bytes(), other.secret.as_bytes())
+    }
+}
+
+impl Eq for ClientKeypair {}
+
+/// Encryption channel between a key pair and one peer, e.g. the connected wallet.
+///
+/// The shared key is computed once, where the free functions compute it again for every
+/// message: prefer a channel when exchanging many messages with the same peer.
+///
+/// # Example
+///
+/// ```
+/// use ton_connect::crypto::{ClientKeypair, PeerChannel};
+///
+/// let app = ClientKeypair::generate_random();
+/// let wallet = ClientKeypair::generate_random();
+/// let app_channel = PeerChannel::from_hex(&app, &wallet.get_hex_public()).unwrap();
+/// let wallet_channel = PeerChannel::new(&wallet, app.public.clone());
+///
+/// for id in 0..3 {
+///     let request = format!("{{\"method\":\"disconnect\",\"params\":[],\"id\":\"{}\"}}", id);
+///     let message = app_channel.seal(request.as_bytes()).unwrap();
+///     assert_eq!(wallet_channel.open(&message).unwrap(), request.as_bytes());
+/// }
+/// ```
+pub struct PeerChannel {
+    peer: PublicKey,
+    salsa_box: SalsaBox,
+}
+
+impl PeerChannel {
+    /// Creates the channel between `keypair` and the `peer` public key.
+    pub fn new(keypair: &ClientKeypair, peer: PublicKey) -> Self {
+        Self::with_secret(keypair.secret(), peer)
+    }
+
+    /// Creates the channel between `keypair` and the peer with the hex `peer_pubkey` client ID.
+    pub fn from_hex(keypair: &ClientKeypair, peer_pubkey: &str) -> Result<Self, Error> {
+        Ok(Self::new(keypair, parse_hex_pubkey(peer_pubkey)?))
+    }
+
+    fn with_secret(secret: &SecretKey, peer: PublicKey) -> Self {
+        let salsa_box = create_box(secret, &peer);
+        Self { peer, salsa_box }
+    }
+
+    /// Returns the public key of the peer.
+    pub fn peer(&self) -> &PublicKey {
+        &self.peer
+    }
+
+    /// Returns the hexadecimal public key of the peer, its client ID.
+    pub fn peer_hex(&self) -> String {
+        hex::encode(self.peer.as_bytes())
+    }
+
+    /// Encrypts a plaintext message for the peer.
+    pub fn encrypt(&self, plaintext: &[u8], nonce: &[u8]) -> Result<Vec<u8>, Error> {
+        self.salsa_box
+            .encrypt(box_nonce(nonce)?, plaintext)
+            .map_err(|_| Error::Encryption)
+    }
+
+    /// Encrypts the plaintext held by `buffer` in place for the peer, leaving the ciphertext in it.
+    pub fn encrypt_in_place<B: Buffer>(&self, buffer: &mut B, nonce: &[u8]) -> Result<(), Error> {
+        self.salsa_box
+            .encrypt_in_place(box_nonce(nonce)?, b"", buffer)
+            .map_err(|_| Error::Encryption)
+    }
+
+    /// Decrypts a ciphertext message sent by the peer.
+    pub fn decrypt(&self, ciphertext: &[u8], nonce: &[u8]) -> Result<Vec<u8>, Error> {
+        self.salsa_box
+            .decrypt(box_nonce(nonce)?, ciphertext)
+            .map_err(|_| Error::Decryption)
+    }
+
+    /// Decrypts the ciphertext held by `buffer` in place, leaving the plaintext in it.
+    ///
+    /// On error the content of `buffer` is unspecified.
+    pub fn decrypt_in_place<B: Buffer>(&self, buffer: &mut B, nonce: &[u8]) -> Result<(), Error> {
+        self.salsa_box
+            .decrypt_in_place(box_nonce(nonce)?, b"", buffer)
+            .map_err(|_| Error::Decryption)
+    }
+
+    /// Encrypts `message` for the peer into the envelope of the bridge protocol, like `seal_for`.
+    pub fn seal(&self, message: &[u8]) -> Result<String, Error> {
+        let nonce = generate_random_nonce();
+        let mut envelope = Vec::with_capacity(NONCE_LENGTH + TAG_LENGTH + message.len());
+        envelope.extend_from_slice(&nonce);
+        envelope.extend_from_slice(&self.encrypt(message, &nonce)?);
+        Ok(general_purpose::STANDARD.encode(envelope))
+    }
+
+    /// Decrypts the base64 envelope of a message sent by the peer through the bridge, like `open_from`.
+    pub fn open(&self, message: &str) -> Result<Vec<u8>, Error> {
+        let mut envelope = general_purpose::STANDARD.decode(message)?;
+        self.open_in_place(&mut envelope)?;
+        Ok(envelope)
+    }
+
+    /// Decrypts in place an envelope sent by the peer, already decoded from base64, like `open_in_place`.
+    pub fn open_in_place(&self, envelope: &mut Vec<u8>) -> Result<(), Error> {
+        if envelope.len() < NONCE_LENGTH + TAG_LENGTH {
+            return Err(Error::Bridge("message is too short"));
+        }
+        let mut nonce = [0u8; NONCE_LENGTH];
+        nonce.copy_from_slice(&envelope[..NONCE_LENGTH]);
+        envelope.drain(..NONCE_LENGTH);
+        self.decrypt_in_place(envelope, &nonce)
+    }
+}
+
+impl fmt::Debug for PeerChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PeerChannel")
+            .field("peer", &self.peer_hex())
+            .finish()
+    }
+}
+
+fn box_nonce(nonce: &[u8]) -> Result<&GenericArray<u8, U24>, Error> {
+    if nonce.len() != NONCE_LENGTH {
+        return Err(Error::NonceLength(nonce.len()));
+    }
+    Ok(GenericArray::from_slice(nonce))
+}
+
+fn parse_hex_pubkey(hex_pubkey: &str) -> Result<PublicKey, Error> {
+    let pubkey = hex::decode(hex_pubkey)?;
+    let pubkey: [u8; 32] = pubkey
+        .try_into()
+        .map_err(|pubkey: Vec<u8>| Error::KeyLength(pubkey.len()))?;
+    let pubkey = PublicKey::from(pubkey);
+    Ok(pubkey)
+}
+
+fn parse_hex_secret(hex_secret: &str) -> Result<SecretKey, Error> {
+    let secret = Zeroizing::new(hex::decode(hex_secret)?);
+    if secret.len() != 32 {
+        return Err(Error::KeyLength(secret.len()));
+    }
+    let mut bytes = Zeroizing::new([0u8; 32]);
+    bytes.copy_from_slice(&secret);
+    Ok(SecretKey::from(*bytes))
+}
+
+fn create_box(secret: &SecretKey, public: &PublicKey) -> SalsaBox {
+    SalsaBox::new(public, secret)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -201,7 +582,7 @@ mod tests {
         let nonce = [0u8; NONCE_LENGTH];
         let plaintext = b"Hello, World!";
         let ciphertext =
-            encrypt_message(&sender_keypair.secret, plaintext, &nonce, &receiver_public).unwrap();
+            encrypt_message(sender_keypair.secret(), plaintext, &nonce, &receiver_public).unwrap();
         assert_ne!(ciphertext, plaintext); // Ciphertext should be different from plaintext
     }
 
@@ -218,14 +599,14 @@ mod tests {
         let nonce = [0u8; NONCE_LENGTH];
         let plaintext = b"Hello, World!";
         let ciphertext = encrypt_message(
-            &sender_keypair.secret,
+            sender_keypair.secret(),
             plaintext,
             &nonce,
             &receiver_keypair.get_hex_public(),
         )
         .unwrap();
         let decrypted = decrypt_message(
-            &receiver_keypair.secret,
+            receiver_keypair.secret(),
             &ciphertext,
             &nonce,
             &sender_keypair.get_hex_public(),
@@ -233,22 +614,219 @@ mod tests {
         .unwrap();
         assert_eq!(decrypted, String::from_utf8_lossy(plaintext)); // Decrypted plaintext should match the original plaintext
     }
-}
 
-fn parse_hex_pubkey(hex_pubkey: &str) -> Result<PublicKey, Box<dyn std::error::Error>> {
-    let pubkey = hex::decode(hex_pubkey).expect("invalid hex pubkey");
-    let pubkey: [u8; 32] = pubkey.try_into().map_err(|_| "invalid pubkey length")?;
-    let pubkey = PublicKey::from(pubkey);
-    Ok(pubkey)
-}
+    #[test]
+    fn test_invalid_input() {
+        let keypair = ClientKeypair::generate_random();
+        let nonce = [0u8; NONCE_LENGTH];
+        assert!(matches!(
+            ClientKeypair::from_hex_secret("not hex"),
+            Err(Error::Hex(_))
+        ));
+        assert!(matches!(
+            ClientKeypair::from_hex_secret("e9f5b870"),
+            Err(Error::KeyLength(4))
+        ));
+        assert!(matches!(
+            encrypt_message(keypair.secret(), b"", &nonce, "zz"),
+            Err(Error::Hex(_))
+        ));
+        assert!(matches!(
+            encrypt_message(
+                keypair.secret(),
+                b"",
+                &nonce[..8],
+                &keypair.get_hex_public()
+            ),
+            Err(Error::NonceLength(8))
+        ));
+        assert!(matches!(
+            decrypt_message(
+                keypair.secret(),
+                b"garbage",
+                &nonce,
+                &keypair.get_hex_public()
+            ),
+            Err(Error::Decryption)
+        ));
+    }
 
-fn parse_hex_secret(hex_secret: &str) -> Result<SecretKey, Box<dyn std::error::Error>> {
-    let secret = hex::decode(hex_secret).expect("invalid hex secret");
-    let secret: [u8; 32] = secret.try_into().map_err(|_| "invalid secret length")?;
-    let secret = SecretKey::from(secret);
-    Ok(secret)
-}
+    #[test]
+    fn test_seal_open() {
+        let app = ClientKeypair::generate_random();
+        let wallet = ClientKeypair::generate_random();
+        let message = seal_for(&app, &wallet.get_hex_public(), &[0, 159, 146, 150]).unwrap();
+        let envelope = general_purpose::STANDARD.decode(&message).unwrap();
+        assert_eq!(envelope.len(), NONCE_LENGTH + TAG_LENGTH + 4);
 
-fn create_box(secret: &SecretKey, public: &PublicKey) -> SalsaBox {
-    SalsaBox::new(public, secret)
+        let bridge_message = BridgeMessage {
+            from: app.get_hex_public(),
+            message,
+        };
+        assert_eq!(
+            open_from(&wallet, &bridge_message).unwrap(),
+            vec![0, 159, 146, 150]
+        );
+        assert!(matches!(
+            open_from(&app, &bridge_message),
+            Err(Error::Decryption)
+        ));
+
+        let short = BridgeMessage {
+            from: app.get_hex_public(),
+            message: general_purpose::STANDARD.encode([0u8; NONCE_LENGTH + 8]),
+        };
+        assert!(matches!(open_from(&wallet, &short), Err(Error::Bridge(_))));
+        let invalid = BridgeMessage {
+            from: app.get_hex_public(),
+            message: "not base64!".to_string(),
+        };
+        assert!(matches!(
+            open_from(&wallet, &invalid),
+            Err(Error::Base64(_))
+        ));
+    }
+
+    #[test]
+    fn test_in_place() {
+        let sender = ClientKeypair::generate_random();
+        let receiver = ClientKeypair::generate_random();
+        let nonce = generate_random_nonce();
+        let plaintext = vec![0xc3, 0x28, 0x00, 0xff];
+
+        let mut buffer = Vec::with_capacity(plaintext.len() + TAG_LENGTH);
+        buffer.extend_from_slice(&plaintext);
+        encrypt_in_place(
+            sender.secret(),
+            &mut buffer,
+            &nonce,
+            &receiver.get_hex_public(),
+        )
+        .unwrap();
+        assert_eq!(
+            buffer,
+            encrypt_message(
+                sender.secret(),
+                &plaintext,
+                &nonce,
+                &receiver.get_hex_public()
+            )
+            .unwrap()
+        );
+
+        // Not UTF-8: only the bytes variants accept it.
+        assert!(matches!(
+            decrypt_message(receiver.secret(), &buffer, &nonce, &sender.get_hex_public()),
+            Err(Error::Utf8(_))
+        ));
+        decrypt_in_place(
+            receiver.secret(),
+            &mut buffer,
+            &nonce,
+            &sender.get_hex_public(),
+        )
+        .unwrap();
+        assert_eq!(buffer, plaintext);
+
+        let mut envelope = nonce.to_vec();
+        envelope.extend(
+            encrypt_message(
+                sender.secret(),
+                &plaintext,
+                &nonce,
+                &receiver.get_hex_public(),
+            )
+            .unwrap(),
+        );
+        open_in_place(&receiver, &sender.get_hex_public(), &mut envelope).unwrap();
+        assert_eq!(envelope, plaintext);
+    }
+
+    #[test]
+    fn test_secret_hygiene() {
+        let keypair = ClientKeypair::from_hex_secret(
+            "e9f5b8703c101f2e8abd79789e8996a119d6aa4730eab85751e9ab03861c48a3",
+        )
+        .unwrap();
+        let hex_secret = keypair.get_hex_secret();
+        assert_eq!(
+            hex_secret.expose_secret(),
+            "e9f5b8703c101f2e8abd79789e8996a119d6aa4730eab85751e9ab03861c48a3"
+        );
+        assert_eq!(format!("{:?}", hex_secret), "SecretString(...)");
+        assert!(!format!("{:?}", keypair).contains("e9f5b870"));
+        assert!(format!("{:?}", keypair).contains(&keypair.get_hex_public()));
+
+        assert_eq!(
+            ClientKeypair::from_hex_secret(hex_secret.expose_secret()).unwrap(),
+            keypair
+        );
+        assert_ne!(ClientKeypair::generate_random(), keypair);
+        assert_ne!(SecretString::new("a".to_string()), hex_secret);
+        assert!(!constant_time_eq(b"short", b"longer"));
+    }
+
+    #[test]
+    fn test_peer_channel() {
+        let app = ClientKeypair::generate_random();
+        let wallet = ClientKeypair::generate_random();
+        let channel = PeerChannel::from_hex(&app, &wallet.get_hex_public()).unwrap();
+        assert_eq!(channel.peer_hex(), wallet.get_hex_public());
+        assert!(!format!("{:?}", channel).contains(app.get_hex_secret().expose_secret()));
+
+        // Interoperates with the free functions.
+        let nonce = generate_random_nonce();
+        let ciphertext = channel.encrypt(b"hello", &nonce).unwrap();
+        assert_eq!(
+            ciphertext,
+            encrypt_message(app.secret(), b"hello", &nonce, &wallet.get_hex_public()).unwrap()
+        );
+        let reply = seal_for(&wallet, &app.get_hex_public(), b"world").unwrap();
+        assert_eq!(channel.open(&reply).unwrap(), b"world");
+
+        let other = PeerChannel::from_hex(&app, &app.get_hex_public()).unwrap();
+        assert!(matches!(other.open(&reply), Err(Error::Decryption)));
+        assert!(matches!(
+            channel.decrypt(&ciphertext, &nonce[..12]),
+            Err(Error::NonceLength(12))
+        ));
+        assert!(matches!(
+            PeerChannel::from_hex(&app, "abcd"),
+            Err(Error::KeyLength(2))
+        ));
+    }
+
+    #[test]
+    fn test_derive() {
+        let master_seed: Vec<u8> = (0..32).collect();
+        // Computed with an independent HKDF-SHA256 and X25519 implementation.
+        let vectors = [
+            (
+                &b"user-1"[..],
+                "6a79e771534d6cb86c1b91955bfe46c21dc2a15e5c783081325331ff0880f0b1",
+                "924572814d3bda2e1ccdba86b50d1d76ad1b24cf1ce2eba2c166440f25028c68",
+            ),
+            (
+                &b"user-2"[..],
+                "0d722942c893e355c60f0e1e0e02f6ac43dea06885a74986e06f979b14a082bb",
+                "c45af9dbcc8c3d04cf05c52c25cc3fab6a7ac4016fa61d968eeccdc07e79bc3f",
+            ),
+        ];
+        for (context, secret, public) in vectors.iter() {
+            let keypair = ClientKeypair::derive(&master_seed, context).unwrap();
+            assert_eq!(keypair.get_hex_secret().expose_secret(), *secret);
+            assert_eq!(keypair.get_hex_public(), *public);
+        }
+
+        let keypair = ClientKeypair::derive(&[0x0b; 32], b"").unwrap();
+        assert_eq!(
+            keypair.get_hex_public(),
+            "adbad8e984422aa76ad2bb5f08da4ee746d0ba1eae7289a7d78bc77531589301"
+        );
+
+        assert!(matches!(
+            ClientKeypair::derive(&[0u8; 16], b"user-1"),
+            Err(Error::SeedLength(16))
+        ));
+    }
 }
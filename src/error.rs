@@ -0,0 +1,220 @@
+//! Error type of the crate.
+
+use crate::session::RequestError;
+use std::fmt;
+use std::io;
+use std::string::FromUtf8Error;
+
+/// Errors returned by the functions of the crate.
+///
+/// Some variants only exist with the features that use them, so matches need a wildcard arm.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// A string is not valid hex.
+    Hex(hex::FromHexError),
+    /// A string is not valid base64.
+    Base64(base64::DecodeError),
+    /// A key is not 32 bytes long, holds the actual length.
+    KeyLength(usize),
+    /// A nonce is not `crypto::NONCE_LENGTH` bytes long, holds the actual length.
+    NonceLength(usize),
+    /// A master seed is shorter than `crypto::MIN_MASTER_SEED_LENGTH` bytes, holds the actual length.
+    SeedLength(usize),
+    /// A message could not be encrypted.
+    Encryption,
+    /// A message could not be decrypted: it was tampered with or not encrypted for this key pair.
+    Decryption,
+    /// A decrypted message is not valid UTF-8.
+    Utf8(FromUtf8Error),
+    /// A message does not match the expected JSON format.
+    Json(serde_json::Error),
+    /// An url is invalid.
+    Url(url::ParseError),
+    /// A bridge url or a message received from the bridge is invalid.
+    Bridge(&'static str),
+    /// The HTTP request failed, e.g. to the bridge.
+    #[cfg(feature = "reqwest")]
+    Http(reqwest::Error),
+    /// A TON address is invalid.
+    Address(&'static str),
+    /// A TON Connect universal link is invalid.
+    Link(&'static str),
+    /// An app manifest is invalid.
+    Manifest(&'static str),
+    /// A bag of cells is invalid, or a cell does not hold the expected data.
+    Boc(&'static str),
+    /// A ton_proof or a wallet stateinit does not verify.
+    Verification(&'static str),
+    /// The wallet did not answer a request.
+    Request(RequestError),
+    /// The app is not connected to a wallet.
+    NotConnected,
+    /// The app is already connected to a wallet.
+    AlreadyConnected,
+    /// A session could not be read or written.
+    Store(&'static str),
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// The SQLite database failed.
+    #[cfg(feature = "rusqlite")]
+    Sqlite(rusqlite::Error),
+    /// The bridge server failed.
+    #[cfg(feature = "hyper")]
+    Server(hyper::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Hex(err) => write!(f, "invalid hex: {}", err),
+            Error::Base64(err) => write!(f, "invalid base64: {}", err),
+            Error::KeyLength(len) => write!(f, "invalid key length: {} bytes, expected 32", len),
+            Error::NonceLength(len) => write!(
+                f,
+                "invalid nonce length: {} bytes, expected {}",
+                len,
+                crate::crypto::NONCE_LENGTH
+            ),
+            Error::SeedLength(len) => write!(
+                f,
+                "invalid master seed length: {} bytes, must be at least {}",
+                len,
+                crate::crypto::MIN_MASTER_SEED_LENGTH
+            ),
+            Error::Encryption => write!(f, "encryption failed"),
+            Error::Decryption => write!(f, "decryption failed"),
+            Error::Utf8(err) => write!(f, "invalid UTF-8: {}", err),
+            Error::Json(err) => write!(f, "invalid JSON: {}", err),
+            Error::Url(err) => write!(f, "invalid url: {}", err),
+            Error::Bridge(reason) => write!(f, "bridge error: {}", reason),
+            #[cfg(feature = "reqwest")]
+            Error::Http(err) => write!(f, "HTTP request failed: {}", err),
+            Error::Address(reason) => write!(f, "invalid address: {}", reason),
+            Error::Link(reason) => write!(f, "invalid universal link: {}", reason),
+            Error::Manifest(reason) => write!(f, "invalid app manifest: {}", reason),
+            Error::Boc(reason) => write!(f, "invalid BOC: {}", reason),
+            Error::Verification(reason) => write!(f, "verification failed: {}", reason),
+            Error::Request(err) => err.fmt(f),
+            Error::NotConnected => write!(f, "wallet is not connected"),
+            Error::AlreadyConnected => write!(f, "wallet is already connected, disconnect first"),
+            Error::Store(reason) => write!(f, "session store failed: {}", reason),
+            Error::Io(err) => err.fmt(f),
+            #[cfg(feature = "rusqlite")]
+            Error::Sqlite(err) => write!(f, "SQLite failed: {}", err),
+            #[cfg(feature = "hyper")]
+            Error::Server(err) => write!(f, "bridge server failed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Hex(err) => Some(err),
+            Error::Base64(err) => Some(err),
+            Error::Utf8(err) => Some(err),
+            Error::Json(err) => Some(err),
+            Error::Url(err) => Some(err),
+            #[cfg(feature = "reqwest")]
+            Error::Http(err) => Some(err),
+            Error::Request(err) => Some(err),
+            Error::Io(err) => Some(err),
+            #[cfg(feature = "rusqlite")]
+            Error::Sqlite(err) => Some(err),
+            #[cfg(feature = "hyper")]
+            Error::Server(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<hex::FromHexError> for Error {
+    fn from(err: hex::FromHexError) -> Self {
+        Error::Hex(err)
+    }
+}
+
+impl From<base64::DecodeError> for Error {
+    fn from(err: base64::DecodeError) -> Self {
+        Error::Base64(err)
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(err: FromUtf8Error) -> Self {
+        Error::Utf8(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+impl From<url::ParseError> for Error {
+    fn from(err: url::ParseError) -> Self {
+        Error::Url(err)
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Http(err)
+    }
+}
+
+impl From<RequestError> for Error {
+    fn from(err: RequestError) -> Self {
+        Error::Request(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Self {
+        Error::Sqlite(err)
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl From<hyper::Error> for Error {
+    fn from(err: hyper::Error) -> Self {
+        Error::Server(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<Error>();
+    }
+
+    #[test]
+    fn test_error_display() {
+        assert_eq!(
+            Error::KeyLength(31).to_string(),
+            "invalid key length: 31 bytes, expected 32"
+        );
+        assert_eq!(
+            Error::SeedLength(16).to_string(),
+            "invalid master seed length: 16 bytes, must be at least 32"
+        );
+        assert_eq!(
+            Error::from(hex::decode("zz").unwrap_err()).to_string(),
+            "invalid hex: Invalid character 'z' at position 0"
+        );
+    }
+}
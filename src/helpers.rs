@@ -1,6 +1,7 @@
+use crate::types::{ConnectRequest, Topic};
+use crate::Error;
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use serde_json;
-use types::{ConnectRequest, Topic};
 use url::Url;
 
 /// Creates a universal link for TON Connect using the provided wallet universal URL, client ID, and ConnectRequest.
@@ -30,7 +31,7 @@ pub fn create_universal_link(
     wallet_universal_url: &str,
     client_id: &str,
     connect_request: &ConnectRequest,
-) -> Result<String, Box<dyn std::error::Error>> {
+) -> Result<String, Error> {
     let connect_request = serde_json::to_string(&connect_request)?;
     let connect_request = utf8_percent_encode(&connect_request, NON_ALPHANUMERIC);
     let universal_link = format!(
@@ -58,20 +59,23 @@ pub fn create_universal_link(
 /// let url = create_listen_url(&bridge_url, &clients_ids, &topics).unwrap();
 /// println!("Listen URL: {}", url);
 /// ```
+#[allow(clippy::ptr_arg)]
 pub fn create_listen_url(
     bridge_url: &str,
     clients_ids: &Vec<String>,
     topics: &Option<Vec<Topic>>,
-) -> Result<String, Box<dyn std::error::Error>> {
+) -> Result<String, Error> {
     let mut url = Url::parse(bridge_url)?;
 
     {
-        let mut path_segments = url.path_segments_mut().map_err(|_| "cannot be base")?;
+        let mut path_segments = url
+            .path_segments_mut()
+            .map_err(|_| Error::Bridge("bridge url cannot be a base"))?;
         path_segments.push("events");
     }
 
     if clients_ids.is_empty() {
-        return Err("client_ids is empty".into());
+        return Err(Error::Bridge("client_ids is empty"));
     }
 
     {
@@ -93,6 +97,72 @@ pub fn create_listen_url(
     Ok(url.to_string())
 }
 
+/// Creates a message URL for posting a message to the receiver through the bridge, using the provided bridge URL, sender client ID, receiver client ID, TTL in seconds and optional topic.
+///
+/// # Example
+///
+/// ```
+/// use ton_connect::types::Topic;
+/// use ton_connect::helpers::create_message_url;
+///
+/// let bridge_url = "https://bridge.tonapi.io/bridge";
+/// let client_id = "a3baaa66a1eee1dbe79058aca5980a2222fcc418508635f9317e9dc8c3108201";
+/// let to = "ac02417ce252b719c9fea60404a5b34edddb3b5dd5b1d6a45764b34ce92eb33b";
+/// let topic = Some(Topic::SendTransaction);
+///
+/// let url = create_message_url(bridge_url, client_id, to, 300, &topic).unwrap();
+/// println!("Message URL: {}", url);
+/// ```
+pub fn create_message_url(
+    bridge_url: &str,
+    client_id: &str,
+    to: &str,
+    ttl: u64,
+    topic: &Option<Topic>,
+) -> Result<String, Error> {
+    let mut url = Url::parse(bridge_url)?;
+
+    {
+        let mut path_segments = url
+            .path_segments_mut()
+            .map_err(|_| Error::Bridge("bridge url cannot be a base"))?;
+        path_segments.push("message");
+    }
+
+    url.query_pairs_mut()
+        .append_pair("client_id", client_id)
+        .append_pair("to", to)
+        .append_pair("ttl", &ttl.to_string());
+
+    if let Some(topic) = topic.as_ref() {
+        url.query_pairs_mut()
+            .append_pair("topic", &topic_name(topic));
+    }
+
+    Ok(url.to_string())
+}
+
+/// Checks that `client_id` is a hex public key, as returned by `ClientKeypair::get_hex_public`.
+///
+/// # Example
+///
+/// ```
+/// use ton_connect::helpers::is_client_id;
+///
+/// assert!(is_client_id("a3baaa66a1eee1dbe79058aca5980a2222fcc418508635f9317e9dc8c3108201"));
+/// assert!(!is_client_id("a3baaa66"));
+/// ```
+pub fn is_client_id(client_id: &str) -> bool {
+    client_id.len() == 64 && client_id.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+fn topic_name(topic: &Topic) -> String {
+    serde_json::to_string(topic)
+        .expect("cannot serialize topic")
+        .trim_matches('"')
+        .to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -131,4 +201,28 @@ mod tests {
         let url = create_listen_url(&bridge_url, &clients_ids, &None).unwrap();
         assert_eq!(url.to_string(), "https://bridge.tonapi.io/bridge/events?client_id=a3baaa66a1eee1dbe79058aca5980a2222fcc418508635f9317e9dc8c3108201");
     }
+
+    #[test]
+    fn test_message_url_create() {
+        let bridge_url = "https://bridge.tonapi.io/bridge";
+        let client_id = "a3baaa66a1eee1dbe79058aca5980a2222fcc418508635f9317e9dc8c3108201";
+        let to = "ac02417ce252b719c9fea60404a5b34edddb3b5dd5b1d6a45764b34ce92eb33b";
+
+        let url =
+            create_message_url(bridge_url, client_id, to, 300, &Some(Topic::SignData)).unwrap();
+        assert_eq!(url, "https://bridge.tonapi.io/bridge/message?client_id=a3baaa66a1eee1dbe79058aca5980a2222fcc418508635f9317e9dc8c3108201&to=ac02417ce252b719c9fea60404a5b34edddb3b5dd5b1d6a45764b34ce92eb33b&ttl=300&topic=signData");
+
+        let url = create_message_url(bridge_url, client_id, to, 60, &None).unwrap();
+        assert_eq!(url, "https://bridge.tonapi.io/bridge/message?client_id=a3baaa66a1eee1dbe79058aca5980a2222fcc418508635f9317e9dc8c3108201&to=ac02417ce252b719c9fea60404a5b34edddb3b5dd5b1d6a45764b34ce92eb33b&ttl=60");
+    }
+
+    #[test]
+    fn test_is_client_id() {
+        let public = crate::crypto::ClientKeypair::generate_random().get_hex_public();
+        assert!(is_client_id(&public));
+        assert!(is_client_id(&public.to_uppercase()));
+        assert!(!is_client_id(&public[1..]));
+        assert!(!is_client_id(&format!("{}0", public)));
+        assert!(!is_client_id(&format!("g{}", &public[1..])));
+    }
 }
@@ -1,11 +1,46 @@
 pub extern crate base64;
+#[cfg(feature = "bytes")]
+extern crate bytes;
 extern crate crypto_box;
+extern crate ed25519_dalek;
+#[cfg(feature = "futures")]
+extern crate futures;
+extern crate hkdf;
+#[cfg(feature = "hyper")]
+extern crate hyper;
 extern crate mime;
 extern crate percent_encoding;
+#[cfg(feature = "reqwest")]
+extern crate reqwest;
+#[cfg(feature = "rusqlite")]
+extern crate rusqlite;
 extern crate serde;
 extern crate serde_json;
+extern crate sha2;
+extern crate subtle;
+#[cfg(feature = "tokio")]
+extern crate tokio;
 extern crate url;
+extern crate zeroize;
 
+pub mod address;
+pub mod boc;
+#[cfg(any(feature = "blocking", feature = "async"))]
+pub mod bridge;
+#[cfg(feature = "bridge-server")]
+pub mod bridge_server;
+#[cfg(feature = "blocking")]
+pub mod connector;
 pub mod crypto;
+mod error;
 pub mod helpers;
+pub mod proof;
+pub mod session;
+pub mod state_init;
+#[cfg(test)]
+mod test_fixtures;
 pub mod types;
+pub mod wallet;
+pub mod wallets;
+
+pub use error::Error;
@@ -0,0 +1,367 @@
+//! Signing and verification of the `ton_proof` item the wallet returns on connect.
+//!
+//! The wallet signs the connected address, the app domain, a timestamp and the payload
+//! requested by the app, so a backend can check that the user owns the address.
+
+use crate::address::TonAddress;
+use crate::types::{Timestamp, TonProofDomain, TonProofItemReplySuccessData};
+use crate::Error;
+use base64::{engine::general_purpose, Engine as _};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::convert::TryInto;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Prefix of the message the wallet signs for a `ton_proof`.
+pub const TON_PROOF_PREFIX: &[u8] = b"ton-proof-item-v2/";
+/// Prefix of the hash of the proof message the wallet signs.
+pub const TON_CONNECT_PREFIX: &[u8] = b"ton-connect";
+
+/// Verifies a `ton_proof` returned by the wallet for `address`.
+///
+/// Checks that the proof was made for `expected_domain`, that it is not older than `max_age`
+/// and that it is signed with the ed25519 `public_key` (HEX string without 0x) the wallet reported.
+/// The caller must check that the payload is the one it issued.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use ton_connect::address::TonAddress;
+/// use ton_connect::proof::verify_ton_proof;
+/// use ton_connect::types::TonProofItemReplySuccessData;
+///
+/// let proof: TonProofItemReplySuccessData = serde_json::from_str(
+///     "{\"timestamp\":1700000000,\"domain\":{\"lengthBytes\":14,\"value\":\"ton-connect.rs\"},\"signature\":\"\",\"payload\":\"halo\"}",
+/// ).unwrap();
+/// let address: TonAddress = "0:dc69be3a989b1513f14e9e4ecc46ff6753770a2b199138708ff66238c2719e99"
+///     .parse()
+///     .unwrap();
+/// let result = verify_ton_proof(
+///     &address,
+///     "321b717a3a455096b6c3d20ad3f66207125557dacbb888fca9e00023aa89bfff",
+///     &proof,
+///     "ton-connect.rs",
+///     Duration::from_secs(15 * 60),
+/// );
+/// assert!(result.is_err());
+/// ```
+pub fn verify_ton_proof(
+    address: &TonAddress,
+    public_key: &str,
+    proof: &TonProofItemReplySuccessData,
+    expected_domain: &str,
+    max_age: Duration,
+) -> Result<(), Error> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    verify_ton_proof_at(address, public_key, proof, expected_domain, max_age, now)
+}
+
+fn verify_ton_proof_at(
+    address: &TonAddress,
+    public_key: &str,
+    proof: &TonProofItemReplySuccessData,
+    expected_domain: &str,
+    max_age: Duration,
+    now: u64,
+) -> Result<(), Error> {
+    if proof.domain.value != expected_domain {
+        return Err(Error::Verification("ton_proof domain mismatch"));
+    }
+    if proof.domain.length_bytes as usize != proof.domain.value.len() {
+        return Err(Error::Verification("ton_proof domain length mismatch"));
+    }
+
+    let timestamp = proof
+        .timestamp
+        .as_u64()
+        .ok_or(Error::Verification("invalid ton_proof timestamp"))?;
+    if now.abs_diff(timestamp) > max_age.as_secs() {
+        return Err(Error::Verification("ton_proof is expired"));
+    }
+
+    let public_key: [u8; 32] = hex::decode(public_key)?
+        .try_into()
+        .map_err(|public_key: Vec<u8>| Error::KeyLength(public_key.len()))?;
+    let public_key = VerifyingKey::from_bytes(&public_key)
+        .map_err(|_| Error::Verification("invalid public key"))?;
+    let signature = general_purpose::STANDARD.decode(&proof.signature)?;
+    let signature = Signature::from_slice(&signature)
+        .map_err(|_| Error::Verification("invalid ton_proof signature"))?;
+
+    let message = ton_proof_message(
+        address.workchain(),
+        address.hash(),
+        &proof.domain.value,
+        timestamp,
+        &proof.payload,
+    );
+    public_key
+        .verify(&message, &signature)
+        .map_err(|_| Error::Verification("invalid ton_proof signature"))?;
+    Ok(())
+}
+
+/// Signs a `ton_proof` of `address` for the app at `domain`, with the wallet ed25519 `signing_key`.
+///
+/// `domain` is the host of the app manifest url, e.g. `ton-connect.rs`, and `payload` is the one
+/// of the `TonProofItem` requested by the app. The proof is timestamped with the current time.
+///
+/// # Example
+///
+/// ```
+/// use ed25519_dalek::SigningKey;
+/// use std::time::Duration;
+/// use ton_connect::address::TonAddress;
+/// use ton_connect::proof::{sign_ton_proof, verify_ton_proof};
+///
+/// let signing_key = SigningKey::from_bytes(&[7; 32]);
+/// let address: TonAddress = "0:dc69be3a989b1513f14e9e4ecc46ff6753770a2b199138708ff66238c2719e99"
+///     .parse()
+///     .unwrap();
+/// let proof = sign_ton_proof(&signing_key, &address, "ton-connect.rs", "halo");
+/// assert_eq!(proof.domain.length_bytes, 14);
+///
+/// let public_key = hex::encode(signing_key.verifying_key().as_bytes());
+/// verify_ton_proof(&address, &public_key, &proof, "ton-connect.rs", Duration::from_secs(60)).unwrap();
+/// ```
+pub fn sign_ton_proof(
+    signing_key: &SigningKey,
+    address: &TonAddress,
+    domain: &str,
+    payload: &str,
+) -> TonProofItemReplySuccessData {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    sign_ton_proof_at(signing_key, address, domain, payload, now)
+}
+
+pub(crate) fn sign_ton_proof_at(
+    signing_key: &SigningKey,
+    address: &TonAddress,
+    domain: &str,
+    payload: &str,
+    timestamp: u64,
+) -> TonProofItemReplySuccessData {
+    let message = ton_proof_message(
+        address.workchain(),
+        address.hash(),
+        domain,
+        timestamp,
+        payload,
+    );
+    let signature = signing_key.sign(&message);
+    TonProofItemReplySuccessData {
+        timestamp: Timestamp::NumberValue(timestamp),
+        domain: TonProofDomain {
+            length_bytes: domain.len() as u32,
+            value: domain.to_string(),
+        },
+        signature: general_purpose::STANDARD.encode(signature.to_bytes()),
+        payload: payload.to_string(),
+    }
+}
+
+/// Builds the 32 bytes the wallet signs:
+/// `sha256(0xffff ++ "ton-connect" ++ sha256("ton-proof-item-v2/" ++ workchain ++ hash ++ domain length ++ domain ++ timestamp ++ payload))`.
+fn ton_proof_message(
+    workchain: i32,
+    hash: &[u8; 32],
+    domain: &str,
+    timestamp: u64,
+    payload: &str,
+) -> [u8; 32] {
+    let message = Sha256::new()
+        .chain_update(TON_PROOF_PREFIX)
+        .chain_update(workchain.to_be_bytes())
+        .chain_update(hash)
+        .chain_update((domain.len() as u32).to_le_bytes())
+        .chain_update(domain.as_bytes())
+        .chain_update(timestamp.to_le_bytes())
+        .chain_update(payload.as_bytes())
+        .finalize();
+    Sha256::new()
+        .chain_update([0xff, 0xff])
+        .chain_update(TON_CONNECT_PREFIX)
+        .chain_update(message)
+        .finalize()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "0:dc69be3a989b1513f14e9e4ecc46ff6753770a2b199138708ff66238c2719e99";
+    const TIMESTAMP: u64 = 1700000000;
+
+    fn signed_proof(signing_key: &SigningKey) -> TonProofItemReplySuccessData {
+        // "ton-proof-item-v2/" ++ workchain (BE) ++ hash ++ domain length (LE) ++ domain ++ timestamp (LE) ++ payload
+        let message = hex::decode(
+            "746f6e2d70726f6f662d6974656d2d76322f\
+             00000000\
+             dc69be3a989b1513f14e9e4ecc46ff6753770a2b199138708ff66238c2719e99\
+             0e000000\
+             746f6e2d636f6e6e6563742e7273\
+             00f1536500000000\
+             68616c6f",
+        )
+        .unwrap();
+        let full_message = [&[0xff, 0xff][..], b"ton-connect", &Sha256::digest(&message)].concat();
+        let signature = signing_key.sign(&Sha256::digest(&full_message));
+        TonProofItemReplySuccessData {
+            timestamp: Timestamp::NumberValue(TIMESTAMP),
+            domain: TonProofDomain {
+                length_bytes: 14,
+                value: "ton-connect.rs".to_string(),
+            },
+            signature: general_purpose::STANDARD.encode(signature.to_bytes()),
+            payload: "halo".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_verify_ton_proof() {
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let public_key = hex::encode(signing_key.verifying_key().as_bytes());
+        let proof = signed_proof(&signing_key);
+        let max_age = Duration::from_secs(900);
+
+        verify_ton_proof_at(
+            &ADDRESS.parse().unwrap(),
+            &public_key,
+            &proof,
+            "ton-connect.rs",
+            max_age,
+            TIMESTAMP + 60,
+        )
+        .unwrap();
+
+        assert!(verify_ton_proof_at(
+            &ADDRESS.parse().unwrap(),
+            &public_key,
+            &proof,
+            "evil.example",
+            max_age,
+            TIMESTAMP
+        )
+        .is_err());
+        assert!(verify_ton_proof_at(
+            &ADDRESS.parse().unwrap(),
+            &public_key,
+            &proof,
+            "ton-connect.rs",
+            max_age,
+            TIMESTAMP + 901
+        )
+        .is_err());
+        let other_address = "0:0000000000000000000000000000000000000000000000000000000000000000";
+        assert!(verify_ton_proof_at(
+            &other_address.parse().unwrap(),
+            &public_key,
+            &proof,
+            "ton-connect.rs",
+            max_age,
+            TIMESTAMP
+        )
+        .is_err());
+        let other_key = hex::encode(
+            SigningKey::from_bytes(&[8u8; 32])
+                .verifying_key()
+                .as_bytes(),
+        );
+        assert!(verify_ton_proof_at(
+            &ADDRESS.parse().unwrap(),
+            &other_key,
+            &proof,
+            "ton-connect.rs",
+            max_age,
+            TIMESTAMP
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_sign_ton_proof() {
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let address: TonAddress = ADDRESS.parse().unwrap();
+        let proof = sign_ton_proof_at(&signing_key, &address, "ton-connect.rs", "halo", TIMESTAMP);
+        let expected = signed_proof(&signing_key);
+        assert_eq!(proof.signature, expected.signature);
+        assert_eq!(proof.timestamp.as_u64(), Some(TIMESTAMP));
+        assert_eq!(
+            serde_json::to_value(&proof).unwrap(),
+            serde_json::to_value(&expected).unwrap()
+        );
+
+        // The domain length counts bytes, not characters.
+        let public_key = hex::encode(signing_key.verifying_key().as_bytes());
+        let proof = sign_ton_proof(&signing_key, &address, "тон.рф", "halo");
+        assert_eq!(proof.domain.length_bytes, 11);
+        verify_ton_proof(
+            &address,
+            &public_key,
+            &proof,
+            "тон.рф",
+            Duration::from_secs(60),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_verify_tampered_payload() {
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let public_key = hex::encode(signing_key.verifying_key().as_bytes());
+        let mut proof = signed_proof(&signing_key);
+        proof.payload = "hola".to_string();
+        assert!(verify_ton_proof_at(
+            &ADDRESS.parse().unwrap(),
+            &public_key,
+            &proof,
+            "ton-connect.rs",
+            Duration::from_secs(900),
+            TIMESTAMP
+        )
+        .is_err());
+
+        let mut proof = signed_proof(&signing_key);
+        proof.timestamp = Timestamp::StringValue(TIMESTAMP.to_string());
+        verify_ton_proof_at(
+            &ADDRESS.parse().unwrap(),
+            &public_key,
+            &proof,
+            "ton-connect.rs",
+            Duration::from_secs(900),
+            TIMESTAMP,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_verify_known_answer() {
+        // Signed outside of this crate, as a wallet does, over the proof message built from the spec.
+        let proof: TonProofItemReplySuccessData = serde_json::from_str(
+            r#"{
+                "timestamp": 1700000000,
+                "domain": {"lengthBytes": 14, "value": "ton-connect.rs"},
+                "signature": "2sZ1+twkJgpiElvYjVy6RQ5Dwo1VR3n1m44900y1RkBJCcHUBty3f7KkM7+gPO6XvcJ3t/aUpQeWufSPE4D4Ag==",
+                "payload": "halo"
+            }"#,
+        )
+        .unwrap();
+        verify_ton_proof_at(
+            &ADDRESS.parse().unwrap(),
+            "331ec29d818005e792931236fa4961f8ed32bc51df7b7fc45aa44ba20361e9e9",
+            &proof,
+            "ton-connect.rs",
+            Duration::from_secs(900),
+            TIMESTAMP,
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,393 @@
+//! State of a connection between the app and a wallet.
+
+use crate::crypto::{ClientKeypair, SecretString};
+use crate::types::{ConnectPayload, RawWalletResponse, WalletEvent};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(feature = "async")]
+use std::future::Future;
+#[cfg(feature = "async")]
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::Waker;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod store;
+
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteSessionStore;
+pub use self::store::{FileSessionStore, MemorySessionStore, SessionStore};
+
+/// Default time to wait for the wallet to answer a request.
+///
+/// Requests are confirmed by the user in the wallet, so this is generous.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Everything needed to resume a connection after a restart, without a new QR code scan.
+///
+/// # Example
+///
+/// ```
+/// use ton_connect::crypto::ClientKeypair;
+/// use ton_connect::session::{MemorySessionStore, Session, SessionStore};
+///
+/// let keypair = ClientKeypair::generate_random();
+/// let session = Session::new(&keypair, "https://bridge.tonapi.io/bridge");
+/// let store = MemorySessionStore::new();
+/// store.save("user-1", &session).unwrap();
+///
+/// let session = store.load("user-1").unwrap().unwrap();
+/// assert_eq!(session.keypair().unwrap().get_hex_public(), keypair.get_hex_public());
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    /// Secret key of the app keypair, as returned by `ClientKeypair::get_hex_secret`.
+    pub keypair_secret: SecretString,
+    /// Public key of the wallet, its client id on the bridge. Unknown until the wallet connects.
+    pub wallet_public_key: Option<String>,
+    /// Bridge of the wallet.
+    pub bridge_url: String,
+    /// Id of the last bridge event received, to resume listening after it.
+    pub last_event_id: Option<String>,
+    /// Id of the next request sent to the wallet, see `RequestRegistry::next_id`.
+    pub next_request_id: u64,
+    /// Payload of the connect event, with the wallet address and device.
+    pub connect_payload: Option<ConnectPayload>,
+}
+
+impl Session {
+    /// Creates the session of a connection that is not established yet.
+    pub fn new(keypair: &ClientKeypair, bridge_url: &str) -> Self {
+        Self {
+            keypair_secret: keypair.get_hex_secret(),
+            wallet_public_key: None,
+            bridge_url: bridge_url.to_string(),
+            last_event_id: None,
+            next_request_id: 0,
+            connect_payload: None,
+        }
+    }
+
+    /// Restores the app keypair.
+    pub fn keypair(&self) -> Result<ClientKeypair, Error> {
+        ClientKeypair::from_hex_secret(self.keypair_secret.expose_secret())
+    }
+
+    /// Whether the wallet connected and did not disconnect since.
+    pub fn is_connected(&self) -> bool {
+        self.wallet_public_key.is_some() && self.connect_payload.is_some()
+    }
+
+    /// Creates the registry for the requests of this session, continuing its id counter.
+    pub fn request_registry(&self, timeout: Duration) -> RequestRegistry {
+        RequestRegistry::with_next_id(self.next_request_id, timeout)
+    }
+}
+
+/// Reason a pending request got no response.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RequestError {
+    /// The wallet did not answer before the request timeout.
+    Timeout,
+    /// The wallet disconnected before answering.
+    Disconnected,
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RequestError::Timeout => write!(f, "wallet did not answer in time"),
+            RequestError::Disconnected => write!(f, "wallet disconnected"),
+        }
+    }
+}
+
+impl std::error::Error for RequestError {}
+
+/// Allocates ids for the requests sent to the wallet and hands each response to the caller waiting for it.
+///
+/// The id counter is part of the session state: persist `next_id` and restore it with `with_next_id`,
+/// wallets ignore requests with an id that is not greater than the previous one.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use ton_connect::session::RequestRegistry;
+/// use ton_connect::types::RawWalletResponse;
+///
+/// let registry = RequestRegistry::new(Duration::from_secs(60));
+/// let pending = registry.register();
+/// // Send the request with `pending.id()` through the bridge, then feed the responses in.
+/// let response: RawWalletResponse =
+///     serde_json::from_str(&format!("{{\"result\":\"ok\",\"id\":\"{}\"}}", pending.id())).unwrap();
+/// assert!(registry.resolve(response).is_none());
+/// let response = pending.wait().unwrap();
+/// println!("Response: {:?}", response);
+/// ```
+#[derive(Clone)]
+pub struct RequestRegistry {
+    inner: Arc<Mutex<Registry>>,
+    timeout: Duration,
+}
+
+struct Registry {
+    next_id: u64,
+    pending: HashMap<String, Arc<Slot>>,
+}
+
+type Outcome = Result<RawWalletResponse, RequestError>;
+
+#[derive(Default)]
+struct Slot {
+    state: Mutex<SlotState>,
+    ready: Condvar,
+}
+
+#[derive(Default)]
+struct SlotState {
+    outcome: Option<Outcome>,
+    waker: Option<Waker>,
+}
+
+impl Slot {
+    fn complete(&self, outcome: Outcome) {
+        let mut state = lock(&self.state);
+        state.outcome = Some(outcome);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.ready.notify_all();
+    }
+}
+
+impl RequestRegistry {
+    /// Creates a registry for a new session, failing requests not answered within `timeout`.
+    pub fn new(timeout: Duration) -> Self {
+        Self::with_next_id(0, timeout)
+    }
+
+    /// Creates a registry for a restored session, continuing from the persisted id counter.
+    pub fn with_next_id(next_id: u64, timeout: Duration) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Registry {
+                next_id,
+                pending: HashMap::new(),
+            })),
+            timeout,
+        }
+    }
+
+    /// Id the next request will get, to be persisted with the session.
+    pub fn next_id(&self) -> u64 {
+        lock(&self.inner).next_id
+    }
+
+    /// Number of requests waiting for a response.
+    pub fn pending_count(&self) -> usize {
+        lock(&self.inner).pending.len()
+    }
+
+    /// Allocates the id for a new request and starts waiting for its response.
+    pub fn register(&self) -> PendingRequest {
+        let mut registry = lock(&self.inner);
+        let id = registry.next_id.to_string();
+        registry.next_id += 1;
+        let slot = Arc::new(Slot::default());
+        registry.pending.insert(id.clone(), slot.clone());
+        PendingRequest {
+            id,
+            deadline: Instant::now() + self.timeout,
+            slot,
+            registry: self.inner.clone(),
+            #[cfg(feature = "async")]
+            sleep: None,
+        }
+    }
+
+    /// Hands the response to the request waiting for it.
+    ///
+    /// Returns the response back if no request with its id is pending.
+    pub fn resolve(&self, response: RawWalletResponse) -> Option<RawWalletResponse> {
+        let slot = lock(&self.inner).pending.remove(response.id());
+        match slot {
+            Some(slot) => {
+                slot.complete(Ok(response));
+                None
+            }
+            None => Some(response),
+        }
+    }
+
+    /// Fails every pending request when the wallet disconnects.
+    pub fn handle_event(&self, event: &WalletEvent) {
+        if let WalletEvent::Disconnect { .. } = event {
+            self.fail_all(RequestError::Disconnected);
+        }
+    }
+
+    /// Fails every pending request with `error`.
+    pub fn fail_all(&self, error: RequestError) {
+        let pending = std::mem::take(&mut lock(&self.inner).pending);
+        for slot in pending.values() {
+            slot.complete(Err(error));
+        }
+    }
+}
+
+/// A request waiting for the wallet response.
+///
+/// Wait for it with `wait`, or await it with the `async` feature.
+pub struct PendingRequest {
+    id: String,
+    deadline: Instant,
+    slot: Arc<Slot>,
+    registry: Arc<Mutex<Registry>>,
+    #[cfg(feature = "async")]
+    sleep: Option<Pin<Box<tokio::time::Sleep>>>,
+}
+
+impl PendingRequest {
+    /// Id to send the request with.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Blocks until the wallet answers, disconnects or the request times out.
+    pub fn wait(self) -> Result<RawWalletResponse, RequestError> {
+        let mut state = lock(&self.slot.state);
+        loop {
+            if let Some(outcome) = state.outcome.take() {
+                return outcome;
+            }
+            let now = Instant::now();
+            if now >= self.deadline {
+                return Err(RequestError::Timeout);
+            }
+            state = self
+                .slot
+                .ready
+                .wait_timeout(state, self.deadline - now)
+                .expect("request slot poisoned")
+                .0;
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Future for PendingRequest {
+    type Output = Result<RawWalletResponse, RequestError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        {
+            let mut state = lock(&self.slot.state);
+            if let Some(outcome) = state.outcome.take() {
+                return Poll::Ready(outcome);
+            }
+            state.waker = Some(cx.waker().clone());
+        }
+        let deadline = self.deadline;
+        let sleep = self
+            .sleep
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline.into())));
+        match sleep.as_mut().poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(RequestError::Timeout)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        lock(&self.registry).pending.remove(&self.id);
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().expect("request registry poisoned")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::WalletResponse;
+    use std::thread;
+
+    fn response(id: &str) -> RawWalletResponse {
+        WalletResponse::Success {
+            result: serde_json::Value::String(format!("result {}", id)),
+            id: id.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_ids_increase() {
+        let registry = RequestRegistry::with_next_id(41, DEFAULT_REQUEST_TIMEOUT);
+        let first = registry.register();
+        let second = registry.register();
+        assert_eq!(first.id(), "41");
+        assert_eq!(second.id(), "42");
+        assert_eq!(registry.next_id(), 43);
+        assert_eq!(registry.pending_count(), 2);
+        drop(first);
+        assert_eq!(registry.pending_count(), 1);
+    }
+
+    #[test]
+    fn test_resolve_matches_id() {
+        let registry = RequestRegistry::new(DEFAULT_REQUEST_TIMEOUT);
+        let first = registry.register();
+        let second = registry.register();
+
+        let resolver = registry.clone();
+        let handle = thread::spawn(move || {
+            assert!(resolver.resolve(response("1")).is_none());
+            assert!(resolver.resolve(response("0")).is_none());
+            assert!(resolver.resolve(response("7")).is_some());
+        });
+        assert_eq!(second.wait(), Ok(response("1")));
+        assert_eq!(first.wait(), Ok(response("0")));
+        handle.join().unwrap();
+        assert_eq!(registry.pending_count(), 0);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let registry = RequestRegistry::new(Duration::from_millis(50));
+        let pending = registry.register();
+        assert_eq!(pending.wait(), Err(RequestError::Timeout));
+        assert_eq!(registry.pending_count(), 0);
+        assert!(registry.resolve(response("0")).is_some());
+    }
+
+    #[test]
+    fn test_disconnect_fails_pending() {
+        let registry = RequestRegistry::new(DEFAULT_REQUEST_TIMEOUT);
+        let pending = registry.register();
+        registry.handle_event(&WalletEvent::Disconnect { id: 3 });
+        assert_eq!(pending.wait(), Err(RequestError::Disconnected));
+        assert_eq!(registry.next_id(), 1);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_await_response() {
+        let registry = RequestRegistry::new(DEFAULT_REQUEST_TIMEOUT);
+        let pending = registry.register();
+        let resolver = registry.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            resolver.resolve(response("0"));
+        });
+        assert_eq!(pending.await, Ok(response("0")));
+
+        let registry = RequestRegistry::new(Duration::from_millis(20));
+        assert_eq!(registry.register().await, Err(RequestError::Timeout));
+    }
+}
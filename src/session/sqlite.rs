@@ -0,0 +1,303 @@
+use super::store::SessionStore;
+use super::Session;
+use crate::crypto::SecretString;
+use crate::Error;
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction, TransactionBehavior};
+use std::convert::TryFrom;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Schema migrations, applied in order. The number of applied ones is kept in `user_version`.
+const MIGRATIONS: &[&str] = &["CREATE TABLE sessions (
+        key TEXT PRIMARY KEY NOT NULL,
+        keypair_secret TEXT NOT NULL,
+        wallet_public_key TEXT,
+        bridge_url TEXT NOT NULL,
+        last_event_id TEXT,
+        next_request_id INTEGER NOT NULL,
+        connect_payload TEXT,
+        connected INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE INDEX sessions_connected ON sessions (connected);"];
+
+/// Keeps sessions in a SQLite database, one row per session key.
+///
+/// Besides the session itself, each row records whether the wallet is connected and when
+/// the session was last updated, so a backend can find the sessions to resume on startup.
+///
+/// # Example
+///
+/// ```
+/// use ton_connect::crypto::ClientKeypair;
+/// use ton_connect::session::{Session, SessionStore, SqliteSessionStore};
+///
+/// let store = SqliteSessionStore::open_in_memory().unwrap();
+/// let session = Session::new(&ClientKeypair::generate_random(), "https://bridge.tonapi.io/bridge");
+/// store.save("user-1", &session).unwrap();
+///
+/// // Record the last event id received without overwriting concurrent changes.
+/// store
+///     .update("user-1", |session| session.last_event_id = Some("42".to_string()))
+///     .unwrap();
+/// assert_eq!(store.load("user-1").unwrap().unwrap().last_event_id.as_deref(), Some("42"));
+/// ```
+pub struct SqliteSessionStore {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteSessionStore {
+    /// Opens the database at `path`, creating it and migrating its schema if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Opens a database that only lives as long as the store.
+    pub fn open_in_memory() -> Result<Self, Error> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    /// Uses an already opened database, migrating its schema if needed.
+    pub fn from_connection(mut connection: Connection) -> Result<Self, Error> {
+        migrate(&mut connection)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Loads the session, applies `update` and saves it back in a single transaction.
+    ///
+    /// Returns the updated session, or `None` without calling `update` if there is no session
+    /// stored with the key.
+    pub fn update<F>(&self, key: &str, update: F) -> Result<Option<Session>, Error>
+    where
+        F: FnOnce(&mut Session),
+    {
+        let mut connection = self.lock();
+        let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let session = match select(&transaction, key)? {
+            Some(mut session) => {
+                update(&mut session);
+                upsert(&transaction, key, &session)?;
+                Some(session)
+            }
+            None => None,
+        };
+        transaction.commit()?;
+        Ok(session)
+    }
+
+    /// Keys of the sessions with a connected wallet, e.g. to resume listening on startup.
+    pub fn connected_keys(&self) -> Result<Vec<String>, Error> {
+        let connection = self.lock();
+        let mut statement =
+            connection.prepare("SELECT key FROM sessions WHERE connected = 1 ORDER BY key")?;
+        let keys = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(keys)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock().expect("session store poisoned")
+    }
+}
+
+impl SessionStore for SqliteSessionStore {
+    fn save(&self, key: &str, session: &Session) -> Result<(), Error> {
+        let mut connection = self.lock();
+        let transaction = connection.transaction()?;
+        upsert(&transaction, key, session)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn load(&self, key: &str) -> Result<Option<Session>, Error> {
+        let mut connection = self.lock();
+        let transaction = connection.transaction()?;
+        select(&transaction, key)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), Error> {
+        self.lock()
+            .execute("DELETE FROM sessions WHERE key = ?1", params![key])?;
+        Ok(())
+    }
+}
+
+fn migrate(connection: &mut Connection) -> Result<(), Error> {
+    let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > MIGRATIONS.len() {
+        return Err(Error::Store(
+            "session database was created by a newer version",
+        ));
+    }
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", index + 1)?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+fn select(transaction: &Transaction, key: &str) -> Result<Option<Session>, Error> {
+    let row = transaction
+        .query_row(
+            "SELECT keypair_secret, wallet_public_key, bridge_url, last_event_id, next_request_id, connect_payload
+            FROM sessions WHERE key = ?1",
+            params![key],
+            SessionRow::from_row,
+        )
+        .optional()?;
+    row.map(SessionRow::into_session).transpose()
+}
+
+fn upsert(transaction: &Transaction, key: &str, session: &Session) -> Result<(), Error> {
+    let connect_payload = session
+        .connect_payload
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+    let updated_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    transaction.execute(
+        "INSERT INTO sessions (key, keypair_secret, wallet_public_key, bridge_url, last_event_id, next_request_id, connect_payload, connected, updated_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        ON CONFLICT (key) DO UPDATE SET
+            keypair_secret = excluded.keypair_secret,
+            wallet_public_key = excluded.wallet_public_key,
+            bridge_url = excluded.bridge_url,
+            last_event_id = excluded.last_event_id,
+            next_request_id = excluded.next_request_id,
+            connect_payload = excluded.connect_payload,
+            connected = excluded.connected,
+            updated_at = excluded.updated_at",
+        params![
+            key,
+            session.keypair_secret.expose_secret(),
+            session.wallet_public_key,
+            session.bridge_url,
+            session.last_event_id,
+            i64::try_from(session.next_request_id).map_err(|_| Error::Store("request id out of range"))?,
+            connect_payload,
+            session.is_connected(),
+            i64::try_from(updated_at).map_err(|_| Error::Store("time out of range"))?,
+        ],
+    )?;
+    Ok(())
+}
+
+struct SessionRow {
+    keypair_secret: SecretString,
+    wallet_public_key: Option<String>,
+    bridge_url: String,
+    last_event_id: Option<String>,
+    next_request_id: i64,
+    connect_payload: Option<String>,
+}
+
+impl SessionRow {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            keypair_secret: SecretString::new(row.get(0)?),
+            wallet_public_key: row.get(1)?,
+            bridge_url: row.get(2)?,
+            last_event_id: row.get(3)?,
+            next_request_id: row.get(4)?,
+            connect_payload: row.get(5)?,
+        })
+    }
+
+    fn into_session(self) -> Result<Session, Error> {
+        Ok(Session {
+            keypair_secret: self.keypair_secret,
+            wallet_public_key: self.wallet_public_key,
+            bridge_url: self.bridge_url,
+            last_event_id: self.last_event_id,
+            next_request_id: u64::try_from(self.next_request_id)
+                .map_err(|_| Error::Store("request id out of range"))?,
+            connect_payload: self
+                .connect_payload
+                .map(|payload| serde_json::from_str(&payload))
+                .transpose()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::ClientKeypair;
+    use crate::test_fixtures::connected_session;
+
+    fn session() -> Session {
+        let mut session = connected_session();
+        session.next_request_id = 3;
+        session
+    }
+
+    #[test]
+    fn test_save_load_delete() {
+        let store = SqliteSessionStore::open_in_memory().unwrap();
+        let session = session();
+        store.save("user-1", &session).unwrap();
+        store
+            .save(
+                "user-2",
+                &Session::new(&ClientKeypair::generate_random(), ""),
+            )
+            .unwrap();
+
+        let loaded = store.load("user-1").unwrap().unwrap();
+        assert_eq!(
+            serde_json::to_value(&loaded).unwrap(),
+            serde_json::to_value(&session).unwrap()
+        );
+        assert_eq!(store.connected_keys().unwrap(), vec!["user-1".to_string()]);
+
+        store.delete("user-1").unwrap();
+        assert!(store.load("user-1").unwrap().is_none());
+        assert!(store.connected_keys().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_update() {
+        let store = SqliteSessionStore::open_in_memory().unwrap();
+        store.save("user", &session()).unwrap();
+        let updated = store
+            .update("user", |session| {
+                session.next_request_id += 1;
+                session.connect_payload = None;
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.next_request_id, 4);
+        assert!(store.connected_keys().unwrap().is_empty());
+        assert!(store.update("missing", |_| panic!()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_reopen_keeps_sessions() {
+        let path = std::env::temp_dir().join(format!(
+            "ton-connect-sessions-{}.sqlite",
+            std::process::id()
+        ));
+        SqliteSessionStore::open(&path)
+            .unwrap()
+            .save("user", &session())
+            .unwrap();
+        let store = SqliteSessionStore::open(&path).unwrap();
+        assert_eq!(store.load("user").unwrap().unwrap().next_request_id, 3);
+
+        let connection = store.connection.into_inner().unwrap();
+        connection
+            .pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+        assert!(SqliteSessionStore::from_connection(connection).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,203 @@
+use super::Session;
+use crate::Error;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// Persists sessions by a key chosen by the app, e.g. the user id.
+pub trait SessionStore {
+    /// Saves the session, replacing the one stored with the same key.
+    fn save(&self, key: &str, session: &Session) -> Result<(), Error>;
+
+    /// Loads the session stored with the key, if any.
+    fn load(&self, key: &str) -> Result<Option<Session>, Error>;
+
+    /// Deletes the session stored with the key. Deleting a missing session is not an error.
+    fn delete(&self, key: &str) -> Result<(), Error>;
+}
+
+/// Keeps sessions in memory, for tests and short-lived processes.
+#[derive(Debug, Default)]
+pub struct MemorySessionStore {
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl MemorySessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    fn save(&self, key: &str, session: &Session) -> Result<(), Error> {
+        lock(&self.sessions).insert(key.to_string(), session.clone());
+        Ok(())
+    }
+
+    fn load(&self, key: &str) -> Result<Option<Session>, Error> {
+        Ok(lock(&self.sessions).get(key).cloned())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), Error> {
+        lock(&self.sessions).remove(key);
+        Ok(())
+    }
+}
+
+/// Keeps sessions in a JSON file, an object mapping the keys to the sessions.
+///
+/// The file is replaced on every change, through a temporary file next to it, so it is
+/// never left half written. On unix the file is readable by its owner only, as it holds the
+/// session secret keys. Use a single store per file.
+#[derive(Debug)]
+pub struct FileSessionStore {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileSessionStore {
+    /// Creates a store in the file at `path`, created on the first save.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn read(&self) -> Result<HashMap<String, Session>, Error> {
+        match fs::read(&self.path) {
+            Ok(content) => Ok(serde_json::from_slice(&content)?),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(err) => Err(Error::Io(err)),
+        }
+    }
+
+    fn write(&self, sessions: &HashMap<String, Session>) -> Result<(), Error> {
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        // A temporary file left by a crash keeps its permissions, so it is created anew.
+        match fs::remove_file(&tmp_path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(Error::Io(err)),
+            _ => {}
+        }
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(&tmp_path)?;
+        file.write_all(&serde_json::to_vec(sessions)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+impl SessionStore for FileSessionStore {
+    fn save(&self, key: &str, session: &Session) -> Result<(), Error> {
+        let _guard = lock(&self.lock);
+        let mut sessions = self.read()?;
+        sessions.insert(key.to_string(), session.clone());
+        self.write(&sessions)
+    }
+
+    fn load(&self, key: &str) -> Result<Option<Session>, Error> {
+        let _guard = lock(&self.lock);
+        Ok(self.read()?.remove(key))
+    }
+
+    fn delete(&self, key: &str) -> Result<(), Error> {
+        let _guard = lock(&self.lock);
+        let mut sessions = self.read()?;
+        if sessions.remove(key).is_some() {
+            self.write(&sessions)?;
+        }
+        Ok(())
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().expect("session store poisoned")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::ClientKeypair;
+    use crate::test_fixtures::connected_session;
+    use std::time::Duration;
+
+    fn session() -> Session {
+        let mut session = connected_session();
+        session.last_event_id = Some("1700000000000".to_string());
+        session.next_request_id = 7;
+        session
+    }
+
+    fn assert_same(left: &Session, right: &Session) {
+        assert_eq!(
+            serde_json::to_value(left).unwrap(),
+            serde_json::to_value(right).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_memory_store() {
+        let store = MemorySessionStore::new();
+        let session = session();
+        assert!(store.load("user").unwrap().is_none());
+        store.save("user", &session).unwrap();
+        assert_same(&store.load("user").unwrap().unwrap(), &session);
+        store.delete("user").unwrap();
+        assert!(store.load("user").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_file_store_survives_restart() {
+        let path =
+            std::env::temp_dir().join(format!("ton-connect-sessions-{}.json", std::process::id()));
+        let session = session();
+        {
+            let store = FileSessionStore::new(&path);
+            store.save("user-1", &session).unwrap();
+            store
+                .save(
+                    "user-2",
+                    &Session::new(&ClientKeypair::generate_random(), ""),
+                )
+                .unwrap();
+        }
+
+        let store = FileSessionStore::new(&path);
+        let restored = store.load("user-1").unwrap().unwrap();
+        assert_same(&restored, &session);
+        assert!(restored.is_connected());
+        assert_eq!(
+            restored.request_registry(Duration::from_secs(1)).next_id(),
+            7
+        );
+        assert_eq!(
+            restored.keypair().unwrap().get_hex_public(),
+            session.keypair().unwrap().get_hex_public()
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        store.delete("user-1").unwrap();
+        assert!(store.load("user-1").unwrap().is_none());
+        assert!(store.load("user-2").unwrap().is_some());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,264 @@
+//! Parsing of the wallet contract stateinit returned in the `ton_addr` item.
+//!
+//! The address of a contract is the hash of its stateinit, and the stateinit of the known
+//! wallet contracts holds the wallet public key, so the reported public key can be checked
+//! against the reported address.
+
+use crate::address::TonAddress;
+use crate::boc::{self, Cell};
+use crate::Error;
+use std::convert::TryInto;
+use std::sync::Arc;
+
+/// Stateinit of a contract: its code and initial data.
+#[derive(Debug, Clone)]
+pub struct StateInit {
+    pub code: Option<Arc<Cell>>,
+    pub data: Option<Arc<Cell>>,
+    root: Arc<Cell>,
+}
+
+impl StateInit {
+    /// Parses the stateinit from its cell.
+    pub fn from_cell(root: Arc<Cell>) -> Result<Self, Error> {
+        let mut parser = root.parser();
+        // split_depth:(Maybe (## 5))
+        if parser.load_bit()? {
+            parser.load_uint(5)?;
+        }
+        // special:(Maybe TickTock)
+        if parser.load_bit()? {
+            parser.load_uint(2)?;
+        }
+        let code = parser.load_maybe_ref()?.cloned();
+        let data = parser.load_maybe_ref()?.cloned();
+        Ok(Self { code, data, root })
+    }
+
+    /// Parses the stateinit from a base64 (not url safe) encoded BOC, as in `walletStateInit`.
+    pub fn from_base64(boc: &str) -> Result<Self, Error> {
+        Self::from_cell(boc::from_base64(boc)?)
+    }
+
+    /// Hash of the stateinit cell, which is the address hash of the contract deployed with it.
+    pub fn hash(&self) -> [u8; 32] {
+        self.root.hash()
+    }
+
+    /// Hash of the contract code, resolving library cells to the hash of the code they refer to.
+    pub fn code_hash(&self) -> Option<[u8; 32]> {
+        self.code.as_ref().map(|code| {
+            if code.is_exotic() {
+                code.data()[1..33]
+                    .try_into()
+                    .expect("library cell holds a hash")
+            } else {
+                code.hash()
+            }
+        })
+    }
+}
+
+/// Wallet contracts whose public key can be read from the stateinit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalletVersion {
+    V3R1,
+    V3R2,
+    V4R2,
+    V5R1,
+}
+
+impl WalletVersion {
+    /// Representation hash of the wallet contract code.
+    pub fn code_hash(&self) -> [u8; 32] {
+        let code_hash = match self {
+            WalletVersion::V3R1 => {
+                "b61041a58a7980b946e8fb9e198e3c904d24799ffa36574ea4251c41a566f581"
+            }
+            WalletVersion::V3R2 => {
+                "84dafa449f98a6987789ba232358072bc0f76dc4524002a5d0918b9a75d2d599"
+            }
+            WalletVersion::V4R2 => {
+                "feb5ff6820e2ff0d9483e7e0d62c817d846789fb4ae580c878866d959dabd5c0"
+            }
+            WalletVersion::V5R1 => {
+                "20834b7b72b112147e1b2fb457b84e74d1a30f04f737d4f62a668e9552d2b72f"
+            }
+        };
+        hex::decode(code_hash)
+            .expect("valid code hash")
+            .try_into()
+            .expect("32 bytes code hash")
+    }
+
+    /// Identifies the wallet contract by its code hash.
+    pub fn from_code_hash(code_hash: &[u8; 32]) -> Option<Self> {
+        [
+            WalletVersion::V3R1,
+            WalletVersion::V3R2,
+            WalletVersion::V4R2,
+            WalletVersion::V5R1,
+        ]
+        .iter()
+        .copied()
+        .find(|version| &version.code_hash() == code_hash)
+    }
+
+    /// Reads the public key from the initial data of the wallet contract.
+    fn load_public_key(&self, data: &Cell) -> Result<[u8; 32], Error> {
+        let mut parser = data.parser();
+        match self {
+            // seqno:uint32 subwallet_id:uint32 public_key:bits256
+            WalletVersion::V3R1 | WalletVersion::V3R2 | WalletVersion::V4R2 => {
+                parser.load_uint(32 + 32)?;
+            }
+            // is_signature_allowed:Bool seqno:uint32 wallet_id:uint32 public_key:bits256
+            WalletVersion::V5R1 => {
+                parser.load_bit()?;
+                parser.load_uint(32 + 32)?;
+            }
+        }
+        let public_key = parser.load_bytes(32)?;
+        Ok(public_key.try_into().expect("32 bytes public key"))
+    }
+}
+
+/// Extracts the wallet version and public key from the stateinit of a known wallet contract.
+pub fn wallet_public_key(state_init: &StateInit) -> Result<(WalletVersion, [u8; 32]), Error> {
+    let code_hash = state_init
+        .code_hash()
+        .ok_or(Error::Verification("stateinit has no code"))?;
+    let version = WalletVersion::from_code_hash(&code_hash)
+        .ok_or(Error::Verification("unknown wallet contract"))?;
+    let data = state_init
+        .data
+        .as_ref()
+        .ok_or(Error::Verification("stateinit has no data"))?;
+    let public_key = version.load_public_key(data)?;
+    Ok((version, public_key))
+}
+
+/// Verifies the `ton_addr` item returned by the wallet.
+///
+/// Checks that the hash of `wallet_state_init` is the hash of the raw `address` and that the
+/// `public_key` (HEX string without 0x) is the one stored in the stateinit. Do this before
+/// trusting `proof::verify_ton_proof`, which only checks the signature against the reported key.
+///
+/// # Example
+///
+/// ```
+/// use ton_connect::address::TonAddress;
+/// use ton_connect::state_init::{verify_wallet_state_init, WalletVersion};
+///
+/// let address: TonAddress = "0:dc69be3a989b1513f14e9e4ecc46ff6753770a2b199138708ff66238c2719e99"
+///     .parse()
+///     .unwrap();
+/// let version = verify_wallet_state_init(
+///     &address,
+///     "321b717a3a455096b6c3d20ad3f66207125557dacbb888fca9e00023aa89bfff",
+///     "te6cckECFgEAAwQAAgE0ARUBFP8A9KQT9LzyyAsCAgEgAxACAUgEBwLm0AHQ0wMhcbCSXwTgItdJwSCSXwTgAtMfIYIQcGx1Z70ighBkc3RyvbCSXwXgA/pAMCD6RAHIygfL/8nQ7UTQgQFA1yH0BDBcgQEI9ApvoTGzkl8H4AXTP8glghBwbHVnupI4MOMNA4IQZHN0crqSXwbjDQUGAHgB+gD0BDD4J28iMFAKoSG+8uBQghBwbHVngx6xcIAYUATLBSbPFlj6Ahn0AMtpF8sfUmDLPyDJgED7AAYAilAEgQEI9Fkw7UTQgQFA1yDIAc8W9ADJ7VQBcrCOI4IQZHN0coMesXCAGFAFywVQA88WI/oCE8tqyx/LP8mAQPsAkl8D4gIBIAgPAgEgCQ4CAVgKCwA9sp37UTQgQFA1yH0BDACyMoHy//J0AGBAQj0Cm+hMYAIBIAwNABmtznaiaEAga5Drhf/AABmvHfaiaEAQa5DrhY/AABG4yX7UTQ1wsfgAWb0kK29qJoQICga5D6AhhHDUCAhHpJN9KZEM5pA+n/mDeBKAG3gQFImHFZ8xhAT48oMI1xgg0x/TH9MfAvgju/Jk7UTQ0x/TH9P/9ATRUUO68qFRUbryogX5AVQQZPkQ8qP4ACSkyMsfUkDLH1Iwy/9SEPQAye1U+A8B0wchwACfbFGTINdKltMH1AL7AOgw4CHAAeMAIcAC4wABwAORMOMNA6TIyx8Syx/L/xESExQAbtIH+gDU1CL5AAXIygcVy//J0Hd0gBjIywXLAiLPFlAF+gIUy2sSzMzJc/sAyEAUgQEI9FHypwIAcIEBCNcY+gDTP8hUIEeBAQj0UfKnghBub3RlcHSAGMjLBcsCUAbPFlAE+gIUy2oSyx/LP8lz+wACAGyBAQjXGPoA0z8wUiSBAQj0WfKnghBkc3RycHSAGMjLBcsCUAXPFlAD+gITy2rLHxLLP8lz+wAACvQAye1UAFEAAAAAKamjFzIbcXo6RVCWtsPSCtP2YgcSVVfay7iI/KngACOqib//QMgBKq8=",
+/// ).unwrap();
+/// assert_eq!(version, WalletVersion::V4R2);
+/// ```
+pub fn verify_wallet_state_init(
+    address: &TonAddress,
+    public_key: &str,
+    wallet_state_init: &str,
+) -> Result<WalletVersion, Error> {
+    let state_init = StateInit::from_base64(wallet_state_init)?;
+    if &state_init.hash() != address.hash() {
+        return Err(Error::Verification("stateinit does not match the address"));
+    }
+    let (version, state_init_public_key) = wallet_public_key(&state_init)?;
+    if hex::decode(public_key)? != state_init_public_key {
+        return Err(Error::Verification(
+            "public key does not match the stateinit",
+        ));
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "0:dc69be3a989b1513f14e9e4ecc46ff6753770a2b199138708ff66238c2719e99";
+    const PUBLIC_KEY: &str = "321b717a3a455096b6c3d20ad3f66207125557dacbb888fca9e00023aa89bfff";
+    const V4R2_STATE_INIT: &str = "te6cckECFgEAAwQAAgE0ARUBFP8A9KQT9LzyyAsCAgEgAxACAUgEBwLm0AHQ0wMhcbCSXwTgItdJwSCSXwTgAtMfIYIQcGx1Z70ighBkc3RyvbCSXwXgA/pAMCD6RAHIygfL/8nQ7UTQgQFA1yH0BDBcgQEI9ApvoTGzkl8H4AXTP8glghBwbHVnupI4MOMNA4IQZHN0crqSXwbjDQUGAHgB+gD0BDD4J28iMFAKoSG+8uBQghBwbHVngx6xcIAYUATLBSbPFlj6Ahn0AMtpF8sfUmDLPyDJgED7AAYAilAEgQEI9Fkw7UTQgQFA1yDIAc8W9ADJ7VQBcrCOI4IQZHN0coMesXCAGFAFywVQA88WI/oCE8tqyx/LP8mAQPsAkl8D4gIBIAgPAgEgCQ4CAVgKCwA9sp37UTQgQFA1yH0BDACyMoHy//J0AGBAQj0Cm+hMYAIBIAwNABmtznaiaEAga5Drhf/AABmvHfaiaEAQa5DrhY/AABG4yX7UTQ1wsfgAWb0kK29qJoQICga5D6AhhHDUCAhHpJN9KZEM5pA+n/mDeBKAG3gQFImHFZ8xhAT48oMI1xgg0x/TH9MfAvgju/Jk7UTQ0x/TH9P/9ATRUUO68qFRUbryogX5AVQQZPkQ8qP4ACSkyMsfUkDLH1Iwy/9SEPQAye1U+A8B0wchwACfbFGTINdKltMH1AL7AOgw4CHAAeMAIcAC4wABwAORMOMNA6TIyx8Syx/L/xESExQAbtIH+gDU1CL5AAXIygcVy//J0Hd0gBjIywXLAiLPFlAF+gIUy2sSzMzJc/sAyEAUgQEI9FHypwIAcIEBCNcY+gDTP8hUIEeBAQj0UfKnghBub3RlcHSAGMjLBcsCUAbPFlAE+gIUy2oSyx/LP8lz+wACAGyBAQjXGPoA0z8wUiSBAQj0WfKnghBkc3RycHSAGMjLBcsCUAXPFlAD+gITy2rLHxLLP8lz+wAACvQAye1UAFEAAAAAKamjFzIbcXo6RVCWtsPSCtP2YgcSVVfay7iI/KngACOqib//QMgBKq8=";
+    const V3R2_CODE: &str = "te6cckEBAQEAcQAA3v8AIN0gggFMl7ohggEznLqxn3Gw7UTQ0x/THzHXC//jBOCk8mCDCNcYINMf0x/TH/gjE7vyY+1E0NMf0x/T/9FRMrryoVFEuvKiBPkBVBBV+RDyo/gAkyDXSpbTB9QC+wDo0QGkyMsfyx/L/8ntVBC9ba0=";
+    const V5R1_CODE: &str = "te6ccgECFAEAAoEAART/APSkE/S88sgLAQIBIAIDAgFIBAUBAvIOAtzQINdJwSCRW49jINcLHyCCEGV4dG69IYIQc2ludL2wkl8D4IIQZXh0brqOtIAg1yEB0HTXIfpAMPpE+Cj6RDBYvZFb4O1E0IEBQdch9AWDB/QOb6ExkTDhgEDXIXB/2zzgMSDXSYECgLmRMOBw4hAPAgEgBgcCASAICQAZvl8PaiaECAoOuQ+gLAIBbgoLAgFIDA0AGa3OdqJoQCDrkOuF/8AAGa8d9qJoQBDrkOuFj8AAF7Ml+1E0HHXIdcLH4AARsmL7UTQ1woAgAR4g1wsfghBzaWduuvLgin8PAeaO8O2i7fshgwjXIgKDCNcjIIAg1yHTH9Mf0x/tRNDSANMfINMf0//XCgAK+QFAzPkQmiiUXwrbMeHywIffArNQB7Dy0IRRJbry4IVQNrry4Ib4I7vy0IgikvgA3gGkf8jKAMsfAc8Wye1UIJL4D95w2zzYEAP27aLt+wL0BCFukmwhjkwCIdc5MHCUIccAs44tAdcoIHYeQ2wg10nACPLgkyDXSsAC8uCTINcdBscSwgBSMLDy0InXTNc5MAGk6GwShAe78uCT10rAAPLgk+1V4tIAAcAAkVvg69csCBQgkXCWAdcsCBwS4lIQseMPINdKERITAJYB+kAB+kT4KPpEMFi68uCR7UTQgQFB1xj0BQSdf8jKAEAEgwf0U/Lgi44UA4MH9Fvy4Iwi1woAIW4Bs7Dy0JDiyFADzxYS9ADJ7VQAcjDXLAgkji0h8uCS0gDtRNDSAFETuvLQj1RQMJExnAGBAUDXIdcKAPLgjuLIygBYzxbJ7VST8sCN4gAQk1vbMeHXTNA=";
+
+    fn state_init(code: &str, data: Vec<u8>, data_bits: usize) -> StateInit {
+        let code = boc::from_base64(code).unwrap();
+        let data = Arc::new(Cell::new(data, data_bits, vec![]).unwrap());
+        // No split_depth, no special, code and data present, empty library.
+        let root = Cell::new(vec![0b0011_0000], 5, vec![code, data]).unwrap();
+        StateInit::from_cell(Arc::new(root)).unwrap()
+    }
+
+    #[test]
+    fn test_verify_wallet_state_init() {
+        let version =
+            verify_wallet_state_init(&ADDRESS.parse().unwrap(), PUBLIC_KEY, V4R2_STATE_INIT)
+                .unwrap();
+        assert_eq!(version, WalletVersion::V4R2);
+    }
+
+    #[test]
+    fn test_verify_wallet_state_init_mismatch() {
+        let other_key = "421b717a3a455096b6c3d20ad3f66207125557dacbb888fca9e00023aa89bfff";
+        let other_address = "0:ec69be3a989b1513f14e9e4ecc46ff6753770a2b199138708ff66238c2719e99";
+        assert!(
+            verify_wallet_state_init(&ADDRESS.parse().unwrap(), other_key, V4R2_STATE_INIT)
+                .is_err()
+        );
+        assert!(verify_wallet_state_init(
+            &other_address.parse().unwrap(),
+            PUBLIC_KEY,
+            V4R2_STATE_INIT
+        )
+        .is_err());
+        assert!(
+            verify_wallet_state_init(&ADDRESS.parse().unwrap(), PUBLIC_KEY, V3R2_CODE).is_err()
+        );
+    }
+
+    #[test]
+    fn test_wallet_v3r2_public_key() {
+        // seqno 0, subwallet 698983191, public key.
+        let mut data = vec![0, 0, 0, 0, 0x29, 0xa9, 0xa3, 0x17];
+        data.extend(hex::decode(PUBLIC_KEY).unwrap());
+        let state_init = state_init(V3R2_CODE, data, 32 + 32 + 256);
+        let (version, public_key) = wallet_public_key(&state_init).unwrap();
+        assert_eq!(version, WalletVersion::V3R2);
+        assert_eq!(hex::encode(public_key), PUBLIC_KEY);
+
+        let address = TonAddress::new(0, state_init.hash());
+        let boc = boc::to_base64(&state_init.root);
+        let version = verify_wallet_state_init(&address, PUBLIC_KEY, &boc).unwrap();
+        assert_eq!(version, WalletVersion::V3R2);
+    }
+
+    #[test]
+    fn test_wallet_v5r1_public_key() {
+        // is_signature_allowed, seqno 0, wallet_id 2147483409, public key, empty extensions.
+        let data = hex::decode(
+            "80000000 3fffff88 990db8bd1d22a84b5b61e90569fb3103892aabed65dc447e54f00011d544dfff 80"
+                .replace(' ', ""),
+        )
+        .unwrap();
+        let state_init = state_init(V5R1_CODE, data, 1 + 32 + 32 + 256 + 1);
+        let (version, public_key) = wallet_public_key(&state_init).unwrap();
+        assert_eq!(version, WalletVersion::V5R1);
+        assert_eq!(hex::encode(public_key), PUBLIC_KEY);
+    }
+
+    #[test]
+    fn test_unknown_wallet() {
+        let state_init = state_init(V3R2_CODE, vec![0; 40], 320);
+        assert!(WalletVersion::from_code_hash(&state_init.data.unwrap().hash()).is_none());
+        let root = Cell::new(vec![0], 5, vec![]).unwrap();
+        let state_init = StateInit::from_cell(Arc::new(root)).unwrap();
+        assert!(wallet_public_key(&state_init).is_err());
+    }
+}
@@ -0,0 +1,18 @@
+//! Values shared by the tests of several modules.
+
+use crate::crypto::ClientKeypair;
+use crate::session::Session;
+
+/// Payload of a `connect` event sent by Tonkeeper.
+pub const CONNECT_PAYLOAD: &str = "{\"items\":[{\"name\":\"ton_addr\",\"address\":\"0:dc69be3a989b1513f14e9e4ecc46ff6753770a2b199138708ff66238c2719e99\",\"network\":\"-239\",\"publicKey\":\"321b717a3a455096b6c3d20ad3f66207125557dacbb888fca9e00023aa89bfff\",\"walletStateInit\":\"te6cckEBAQEAAgAAAEysuc0=\"}],\"device\":{\"platform\":\"iphone\",\"appName\":\"Tonkeeper\",\"appVersion\":\"3.0.304\",\"maxProtocolVersion\":2,\"features\":[\"SendTransaction\",{\"name\":\"SendTransaction\",\"maxMessages\":4}]}}";
+
+/// A session connected to a new wallet keypair through the Tonapi bridge.
+pub fn connected_session() -> Session {
+    let mut session = Session::new(
+        &ClientKeypair::generate_random(),
+        "https://bridge.tonapi.io/bridge",
+    );
+    session.wallet_public_key = Some(ClientKeypair::generate_random().get_hex_public());
+    session.connect_payload = Some(serde_json::from_str(CONNECT_PAYLOAD).unwrap());
+    session
+}
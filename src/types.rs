@@ -1,4 +1,5 @@
-use serde::{Deserialize, Serialize};
+use crate::address::TonAddress;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 /// App needs to have its manifest to pass meta information to the wallet.
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,12 +38,14 @@ pub enum ConnectItem {
     TonProofItem { payload: String },
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Topic {
     #[serde(rename = "sendTransaction")]
     SendTransaction,
     #[serde(rename = "signData")]
     SignData,
+    #[serde(rename = "disconnect")]
+    Disconnect,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -51,6 +54,351 @@ pub struct BridgeMessage {
     pub message: String,
 }
 
+/// RPC request sent by the app to the wallet.
+///
+/// Serialized as `{"method": ..., "params": [<params as JSON string>], "id": ...}`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AppRequest {
+    SendTransaction {
+        /// Increasing request counter, used to match the wallet response.
+        id: String,
+        params: SendTransactionRequest,
+    },
+    SignData {
+        /// Increasing request counter, used to match the wallet response.
+        id: String,
+        params: SignDataRequest,
+    },
+    Disconnect {
+        /// Increasing request counter, used to match the wallet response.
+        id: String,
+    },
+}
+
+impl AppRequest {
+    pub fn id(&self) -> &str {
+        match self {
+            AppRequest::SendTransaction { id, .. }
+            | AppRequest::SignData { id, .. }
+            | AppRequest::Disconnect { id } => id,
+        }
+    }
+
+    /// Bridge topic to post the request with.
+    pub fn topic(&self) -> Topic {
+        match self {
+            AppRequest::SendTransaction { .. } => Topic::SendTransaction,
+            AppRequest::SignData { .. } => Topic::SignData,
+            AppRequest::Disconnect { .. } => Topic::Disconnect,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SendTransactionRequest {
+    /// Unix timestamp (seconds) after which the wallet must not send the transaction.
+    #[serde(rename = "validUntil")]
+    pub valid_until: u64,
+    /// Network the transaction is meant for. The wallet rejects it if it is connected to another network.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<NETWORK>,
+    /// Sender address in raw form (`<wc>:<hex>`). The wallet's connected account if omitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// Messages to send from the wallet contract, 1 to `maxMessages` of the wallet.
+    pub messages: Vec<Message>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    /// Receiver address.
+    pub address: String,
+    /// Amount to send in nanotons, as a decimal string.
+    pub amount: String,
+    /// Base64 (not url safe) encoded BOC of the message body cell.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<String>,
+    /// Base64 (not url safe) encoded BOC of the stateinit cell to deploy with the message.
+    #[serde(rename = "stateInit", skip_serializing_if = "Option::is_none")]
+    pub state_init: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignDataRequest {
+    /// Network the data is meant for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<NETWORK>,
+    /// Signer address in raw form (`<wc>:<hex>`). The wallet's connected account if omitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(flatten)]
+    pub payload: SignDataPayload,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum SignDataPayload {
+    /// Human readable text shown to the user.
+    #[serde(rename = "text")]
+    Text { text: String },
+    /// Base64 (not url safe) encoded arbitrary bytes.
+    #[serde(rename = "binary")]
+    Binary { bytes: String },
+    /// Base64 (not url safe) encoded BOC of a cell described by the TL-B `schema`.
+    #[serde(rename = "cell")]
+    Cell { schema: String, cell: String },
+}
+
+/// RPC response sent by the wallet to the app, generic over the method's result and error code types.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WalletResponse<R, C> {
+    Error {
+        error: WalletResponseError<C>,
+        /// Id of the request this response answers.
+        id: String,
+    },
+    Success {
+        result: R,
+        /// Id of the request this response answers.
+        id: String,
+    },
+}
+
+/// Response as received from the bridge, before the caller knows which method it answers.
+pub type RawWalletResponse = WalletResponse<serde_json::Value, u32>;
+pub type SendTransactionResponse = WalletResponse<String, SendTransactionErrorCode>;
+pub type SignDataResponse = WalletResponse<SignDataResult, SignDataErrorCode>;
+pub type DisconnectResponse = WalletResponse<serde_json::Value, DisconnectErrorCode>;
+
+impl<R, C> WalletResponse<R, C> {
+    /// Id of the request this response answers.
+    pub fn id(&self) -> &str {
+        match self {
+            WalletResponse::Error { id, .. } | WalletResponse::Success { id, .. } => id,
+        }
+    }
+}
+
+impl RawWalletResponse {
+    /// Parses the result and error code for the method the request was sent with.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ton_connect::types::{RawWalletResponse, SendTransactionErrorCode, WalletResponse};
+    ///
+    /// let response: RawWalletResponse = serde_json::from_str(
+    ///     "{\"error\":{\"code\":300,\"message\":\"Declined\"},\"id\":\"2\"}",
+    /// ).unwrap();
+    /// match response.parse::<String, SendTransactionErrorCode>().unwrap() {
+    ///     WalletResponse::Error { error, .. } => {
+    ///         assert_eq!(error.code, SendTransactionErrorCode::UserDeclined)
+    ///     }
+    ///     WalletResponse::Success { result, .. } => println!("Signed BOC: {}", result),
+    /// }
+    /// ```
+    pub fn parse<R, C>(self) -> Result<WalletResponse<R, C>, serde_json::Error>
+    where
+        R: DeserializeOwned,
+        C: From<u32>,
+    {
+        match self {
+            WalletResponse::Error { error, id } => Ok(WalletResponse::Error {
+                error: WalletResponseError {
+                    code: C::from(error.code),
+                    message: error.message,
+                    data: error.data,
+                },
+                id,
+            }),
+            WalletResponse::Success { result, id } => Ok(WalletResponse::Success {
+                result: serde_json::from_value(result)?,
+                id,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WalletResponseError<C> {
+    pub code: C,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+/// Result of a successful signData request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignDataResult {
+    /// Base64-encoded signature.
+    pub signature: String,
+    /// Signer address.
+    pub address: TonAddress,
+    /// 64-bit unix epoch time of the signing operation (seconds).
+    pub timestamp: u64,
+    /// App domain name.
+    pub domain: String,
+    /// Payload from the request.
+    pub payload: SignDataPayload,
+}
+
+/// Declares an RPC error code enum that converts to and from the raw `u32` code, keeping unlisted codes in `Unknown`.
+macro_rules! error_code {
+    ($(#[$meta:meta])* $name:ident { $($(#[$variant_meta:meta])* $variant:ident = $code:expr,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+        #[serde(from = "u32", into = "u32")]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)*
+            /// Code not described by the protocol.
+            Unknown(u32),
+        }
+
+        impl From<u32> for $name {
+            fn from(code: u32) -> Self {
+                match code {
+                    $($code => $name::$variant,)*
+                    code => $name::Unknown(code),
+                }
+            }
+        }
+
+        impl From<$name> for u32 {
+            fn from(code: $name) -> Self {
+                match code {
+                    $($name::$variant => $code,)*
+                    $name::Unknown(code) => code,
+                }
+            }
+        }
+    };
+}
+
+error_code!(
+    /// Error codes of the connect_error event.
+    ConnectErrorCode {
+        UnknownError = 0,
+        BadRequest = 1,
+        ManifestNotFound = 2,
+        ManifestContentError = 3,
+        UnknownApp = 100,
+        /// User declined the connection.
+        UserDeclined = 300,
+    }
+);
+
+error_code!(
+    /// Error codes of the ton_proof item reply.
+    TonProofErrorCode {
+        UnknownError = 0,
+        MethodNotSupported = 400,
+    }
+);
+
+error_code!(
+    /// Error codes of the sendTransaction response.
+    SendTransactionErrorCode {
+        UnknownError = 0,
+        BadRequest = 1,
+        UnknownApp = 100,
+        /// User declined the transaction.
+        UserDeclined = 300,
+        MethodNotSupported = 400,
+    }
+);
+
+error_code!(
+    /// Error codes of the signData response.
+    SignDataErrorCode {
+        UnknownError = 0,
+        BadRequest = 1,
+        UnknownApp = 100,
+        /// User declined the request.
+        UserDeclined = 300,
+        MethodNotSupported = 400,
+    }
+);
+
+error_code!(
+    /// Error codes of the disconnect response.
+    DisconnectErrorCode {
+        UnknownError = 0,
+        BadRequest = 1,
+        UnknownApp = 100,
+        MethodNotSupported = 400,
+    }
+);
+
+mod app_request {
+    use super::AppRequest;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Wire form of `AppRequest`, the params are JSON encoded strings.
+    #[derive(Serialize, Deserialize)]
+    struct RawAppRequest {
+        method: String,
+        params: Vec<String>,
+        id: String,
+    }
+
+    impl Serialize for AppRequest {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            use serde::ser::Error;
+
+            let (method, params) = match self {
+                AppRequest::SendTransaction { params, .. } => (
+                    "sendTransaction",
+                    vec![serde_json::to_string(params).map_err(S::Error::custom)?],
+                ),
+                AppRequest::SignData { params, .. } => (
+                    "signData",
+                    vec![serde_json::to_string(params).map_err(S::Error::custom)?],
+                ),
+                AppRequest::Disconnect { .. } => ("disconnect", vec![]),
+            };
+            RawAppRequest {
+                method: method.to_string(),
+                params,
+                id: self.id().to_string(),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for AppRequest {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let raw = RawAppRequest::deserialize(deserializer)?;
+            let id = raw.id;
+            let params = raw
+                .params
+                .first()
+                .ok_or_else(|| de::Error::invalid_length(0, &"one JSON encoded params string"));
+            match raw.method.as_str() {
+                "sendTransaction" => Ok(AppRequest::SendTransaction {
+                    id,
+                    params: serde_json::from_str(params?).map_err(de::Error::custom)?,
+                }),
+                "signData" => Ok(AppRequest::SignData {
+                    id,
+                    params: serde_json::from_str(params?).map_err(de::Error::custom)?,
+                }),
+                "disconnect" => Ok(AppRequest::Disconnect { id }),
+                method => Err(de::Error::unknown_variant(
+                    method,
+                    &["sendTransaction", "signData", "disconnect"],
+                )),
+            }
+        }
+    }
+}
+
 /// Wallet responds with ConnectEvent message if the user approves the request.
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "event")]
@@ -74,35 +422,28 @@ pub enum WalletEvent {
     },
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConnectPayload {
     pub items: Vec<ConnectItemReply>,
     pub device: DeviceInfo,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConnectErrorPayload {
-    /// | Code | Description                   |
-    /// |------|-------------------------------|
-    /// | 0    | Unknown error                 |
-    /// | 1    | Bad request                   |
-    /// | 2    | App manifest not found        |
-    /// | 3    | App manifest content error    |
-    /// | 100  | Unknown app                   |
-    /// | 300  | User declined the connection  |
-    pub code: u32,
+    /// Why the connection failed, see `ConnectErrorCode` for the codes.
+    pub code: ConnectErrorCode,
     pub message: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "name")]
 pub enum ConnectItemReply {
     /// Untrusted data returned by the wallet.
     /// If you need a guarantee that the user owns this address and public key, you need to additionally request a ton_proof.
     #[serde(rename = "ton_addr")]
     TonAddress {
-        /// TON address raw (`0:<hex>`).
-        address: String,
+        /// TON address, sent in raw form (`0:<hex>`).
+        address: TonAddress,
         /// Network `global_id`.
         network: NETWORK,
         /// HEX string without 0x.
@@ -121,7 +462,7 @@ pub enum ConnectItemReply {
     },
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum NETWORK {
     #[serde(rename = "-239")]
     MAINNET,
@@ -129,10 +470,10 @@ pub enum NETWORK {
     TESTNET,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TonProofItemReplySuccessData {
     /// 64-bit unix epoch time of the signing operation (seconds).
-    pub timestamp: timestamp::Timestamp,
+    pub timestamp: Timestamp,
     pub domain: TonProofDomain,
     /// Base64-encoded signature.
     pub signature: String,
@@ -140,15 +481,27 @@ pub struct TonProofItemReplySuccessData {
     pub payload: String,
 }
 
+pub use self::timestamp::Timestamp;
+
 mod timestamp {
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone)]
     pub enum Timestamp {
         StringValue(String),
         NumberValue(u64),
     }
 
+    impl Timestamp {
+        /// Returns the timestamp in seconds, if a string value holds a valid number.
+        pub fn as_u64(&self) -> Option<u64> {
+            match self {
+                Timestamp::StringValue(string_value) => string_value.parse().ok(),
+                Timestamp::NumberValue(number_value) => Some(*number_value),
+            }
+        }
+    }
+
     impl Serialize for Timestamp {
         fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
         where
@@ -179,17 +532,14 @@ mod timestamp {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TonProofItemReplyErrorData {
-    /// | Code | Description                   |
-    /// |------|-------------------------------|
-    /// | 0    | Unknown error                 |
-    /// | 400  | Method is not supported       |
-    pub code: u32,
+    /// Why the proof failed, see `TonProofErrorCode` for the codes.
+    pub code: TonProofErrorCode,
     pub message: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TonProofDomain {
     /// AppDomain length.
     #[serde(rename = "lengthBytes")]
@@ -198,7 +548,7 @@ pub struct TonProofDomain {
     pub value: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeviceInfo {
     pub platform: Platform,
     /// E.g. "Tonkeeper".
@@ -210,13 +560,15 @@ pub struct DeviceInfo {
     #[serde(rename = "maxProtocolVersion")]
     pub max_protocol_version: u32,
     /// List of supported features and methods in RPC.
-    pub features: Vec<feature::Feature>,
+    pub features: Vec<Feature>,
 }
 
+pub use self::feature::Feature;
+
 mod feature {
     use serde::{ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone)]
     pub enum Feature {
         SendTransactionDeprecated,
         SendTransaction { max_messages: u32 },
@@ -323,7 +675,7 @@ mod feature {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Platform {
     #[serde(rename = "iphone")]
     IPhone,
@@ -361,6 +713,139 @@ mod tests {
         assert_eq!(serialized, expected_output);
     }
 
+    #[test]
+    fn test_serialize_app_request() {
+        let request = AppRequest::SendTransaction {
+            id: "4".to_string(),
+            params: SendTransactionRequest {
+                valid_until: 1658253458,
+                network: Some(NETWORK::MAINNET),
+                from: None,
+                messages: vec![Message {
+                    address: "0:412410771DA82CBA306A55FA9E0D43C9D245E38133CB58F1457DFB8D5CD8892F"
+                        .to_string(),
+                    amount: "20000000".to_string(),
+                    payload: None,
+                    state_init: Some(
+                        "te6ccsEBAQEADgAAABgAAAABAAAAAAAAAAAAAAAAl8pgTg==".to_string(),
+                    ),
+                }],
+            },
+        };
+        let serialized = serde_json::to_string(&request).unwrap();
+        let expected_output = "{\"method\":\"sendTransaction\",\"params\":[\"{\\\"validUntil\\\":1658253458,\\\"network\\\":\\\"-239\\\",\\\"messages\\\":[{\\\"address\\\":\\\"0:412410771DA82CBA306A55FA9E0D43C9D245E38133CB58F1457DFB8D5CD8892F\\\",\\\"amount\\\":\\\"20000000\\\",\\\"stateInit\\\":\\\"te6ccsEBAQEADgAAABgAAAABAAAAAAAAAAAAAAAAl8pgTg==\\\"}]}\"],\"id\":\"4\"}";
+        assert_eq!(serialized, expected_output);
+        assert_eq!(
+            serde_json::from_str::<AppRequest>(&serialized).unwrap(),
+            request
+        );
+
+        let request = AppRequest::SignData {
+            id: "5".to_string(),
+            params: SignDataRequest {
+                network: None,
+                from: None,
+                payload: SignDataPayload::Text {
+                    text: "Confirm new 2fa number".to_string(),
+                },
+            },
+        };
+        let serialized = serde_json::to_string(&request).unwrap();
+        let expected_output = "{\"method\":\"signData\",\"params\":[\"{\\\"type\\\":\\\"text\\\",\\\"text\\\":\\\"Confirm new 2fa number\\\"}\"],\"id\":\"5\"}";
+        assert_eq!(serialized, expected_output);
+        assert_eq!(
+            serde_json::from_str::<AppRequest>(&serialized).unwrap(),
+            request
+        );
+
+        let request = AppRequest::Disconnect {
+            id: "6".to_string(),
+        };
+        let serialized = serde_json::to_string(&request).unwrap();
+        assert_eq!(
+            serialized,
+            "{\"method\":\"disconnect\",\"params\":[],\"id\":\"6\"}"
+        );
+        assert_eq!(
+            serde_json::from_str::<AppRequest>(&serialized).unwrap(),
+            request
+        );
+        assert_eq!(request.topic(), Topic::Disconnect);
+    }
+
+    #[test]
+    fn test_deserialize_invalid_app_request() {
+        assert!(serde_json::from_str::<AppRequest>(
+            "{\"method\":\"sendTransaction\",\"params\":[],\"id\":\"1\"}"
+        )
+        .is_err());
+        assert!(serde_json::from_str::<AppRequest>(
+            "{\"method\":\"transfer\",\"params\":[],\"id\":\"1\"}"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_deserialize_wallet_response() {
+        let response: RawWalletResponse =
+            serde_json::from_str("{\"result\":\"te6ccgEBAQEAAgAAAA==\",\"id\":\"7\"}").unwrap();
+        assert_eq!(response.id(), "7");
+        assert_eq!(
+            response
+                .parse::<String, SendTransactionErrorCode>()
+                .unwrap(),
+            SendTransactionResponse::Success {
+                result: "te6ccgEBAQEAAgAAAA==".to_string(),
+                id: "7".to_string(),
+            }
+        );
+
+        let response: SignDataResponse = serde_json::from_str(
+            "{\"error\":{\"code\":300,\"message\":\"User declined\"},\"id\":\"8\"}",
+        )
+        .unwrap();
+        match response {
+            WalletResponse::Error { error, id } => {
+                assert_eq!(error.code, SignDataErrorCode::UserDeclined);
+                assert_eq!(id, "8");
+            }
+            _ => panic!("expected an error response"),
+        }
+
+        let response: DisconnectResponse =
+            serde_json::from_str("{\"error\":{\"code\":42,\"message\":\"Oops\"},\"id\":\"9\"}")
+                .unwrap();
+        let serialized = serde_json::to_string(&response).unwrap();
+        assert_eq!(
+            serialized,
+            "{\"error\":{\"code\":42,\"message\":\"Oops\"},\"id\":\"9\"}"
+        );
+        match response {
+            WalletResponse::Error { error, .. } => {
+                assert_eq!(error.code, DisconnectErrorCode::Unknown(42))
+            }
+            _ => panic!("expected an error response"),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_connect_error() {
+        let input = "{\"event\":\"connect_error\",\"id\":4,\"payload\":{\"code\":300,\"message\":\"User declined\"}}";
+        let deserialized: WalletEvent = serde_json::from_str(input).unwrap();
+        match deserialized {
+            WalletEvent::ConnectError { payload, .. } => {
+                assert_eq!(payload.code, ConnectErrorCode::UserDeclined);
+                assert_eq!(u32::from(payload.code), 300);
+            }
+            _ => panic!("expected a connect_error event"),
+        }
+        assert_eq!(ConnectErrorCode::from(7), ConnectErrorCode::Unknown(7));
+        assert_eq!(
+            TonProofErrorCode::from(400),
+            TonProofErrorCode::MethodNotSupported
+        );
+    }
+
     #[test]
     fn test_deserialize_event() {
         let input = "{\"id\":65,\"event\":\"connect\",\"payload\":{\"items\":[{\"name\":\"ton_addr\",\"address\":\"0:dc69be3a989b1513f14e9e4ecc46ff6753770a2b199138708ff66238c2719e99\",\"network\":\"-239\",\"publicKey\":\"321b717a3a455096b6c3d20ad3f66207125557dacbb888fca9e00023aa89bfff\",\"walletStateInit\":\"te6cckECFgEAAwQAAgE0ARUBFP8A9KQT9LzyyAsCAgEgAxACAUgEBwLm0AHQ0wMhcbCSXwTgItdJwSCSXwTgAtMfIYIQcGx1Z70ighBkc3RyvbCSXwXgA/pAMCD6RAHIygfL/8nQ7UTQgQFA1yH0BDBcgQEI9ApvoTGzkl8H4AXTP8glghBwbHVnupI4MOMNA4IQZHN0crqSXwbjDQUGAHgB+gD0BDD4J28iMFAKoSG+8uBQghBwbHVngx6xcIAYUATLBSbPFlj6Ahn0AMtpF8sfUmDLPyDJgED7AAYAilAEgQEI9Fkw7UTQgQFA1yDIAc8W9ADJ7VQBcrCOI4IQZHN0coMesXCAGFAFywVQA88WI/oCE8tqyx/LP8mAQPsAkl8D4gIBIAgPAgEgCQ4CAVgKCwA9sp37UTQgQFA1yH0BDACyMoHy//J0AGBAQj0Cm+hMYAIBIAwNABmtznaiaEAga5Drhf/AABmvHfaiaEAQa5DrhY/AABG4yX7UTQ1wsfgAWb0kK29qJoQICga5D6AhhHDUCAhHpJN9KZEM5pA+n/mDeBKAG3gQFImHFZ8xhAT48oMI1xgg0x/TH9MfAvgju/Jk7UTQ0x/TH9P/9ATRUUO68qFRUbryogX5AVQQZPkQ8qP4ACSkyMsfUkDLH1Iwy/9SEPQAye1U+A8B0wchwACfbFGTINdKltMH1AL7AOgw4CHAAeMAIcAC4wABwAORMOMNA6TIyx8Syx/L/xESExQAbtIH+gDU1CL5AAXIygcVy//J0Hd0gBjIywXLAiLPFlAF+gIUy2sSzMzJc/sAyEAUgQEI9FHypwIAcIEBCNcY+gDTP8hUIEeBAQj0UfKnghBub3RlcHSAGMjLBcsCUAbPFlAE+gIUy2oSyx/LP8lz+wACAGyBAQjXGPoA0z8wUiSBAQj0WfKnghBkc3RycHSAGMjLBcsCUAXPFlAD+gITy2rLHxLLP8lz+wAACvQAye1UAFEAAAAAKamjFzIbcXo6RVCWtsPSCtP2YgcSVVfay7iI/KngACOqib//QMgBKq8=\"}],\"device\":{\"platform\":\"iphone\",\"appName\":\"Tonkeeper\",\"appVersion\":\"3.0.304\",\"maxProtocolVersion\":2,\"features\":[\"SendTransaction\",{\"name\":\"SendTransaction\",\"maxMessages\":4}]}}}";
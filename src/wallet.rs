@@ -0,0 +1,577 @@
+//! Wallet side of TON Connect: reads the connect request of an app and builds the reply.
+//!
+//! The app shows a link made by `create_universal_link`. The wallet parses it with
+//! `parse_universal_link`, fetches the app manifest, and posts the `connect` event sealed by
+//! `Wallet::connect_message` to the app client id through the bridge, from a session keypair
+//! of the wallet.
+
+use crate::address::TonAddress;
+use crate::crypto::{seal_for, ClientKeypair};
+use crate::helpers::is_client_id;
+use crate::proof::sign_ton_proof_at;
+use crate::types::{
+    AppManifest, ConnectErrorCode, ConnectErrorPayload, ConnectItem, ConnectItemReply,
+    ConnectPayload, ConnectRequest, DeviceInfo, WalletEvent, NETWORK,
+};
+use crate::wallets::WalletsFetcher;
+use crate::Error;
+use ed25519_dalek::SigningKey;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+/// Protocol version of the links and messages this module handles.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Connect request of an app, as read from its universal link.
+#[derive(Debug)]
+pub struct ConnectLink {
+    /// Protocol version, `v` in the link.
+    pub version: u32,
+    /// Client id of the app, `id` in the link, to send the reply to.
+    pub client_id: String,
+    /// Connect request of the app, `r` in the link.
+    pub request: ConnectRequest,
+    /// Where to go after the user answers, `ret` in the link.
+    pub ret: Option<ReturnStrategy>,
+}
+
+/// Where the wallet goes after the user answers a connect request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReturnStrategy {
+    /// Back to the app that opened the link, `back`.
+    Back,
+    /// Stay in the wallet, `none`.
+    None,
+    /// Open an http(s) url.
+    Url(Url),
+}
+
+impl FromStr for ReturnStrategy {
+    type Err = Error;
+
+    fn from_str(ret: &str) -> Result<Self, Self::Err> {
+        match ret {
+            "back" => Ok(ReturnStrategy::Back),
+            "none" => Ok(ReturnStrategy::None),
+            url => http_url(url)
+                .map(ReturnStrategy::Url)
+                .ok_or(Error::Link("invalid ret")),
+        }
+    }
+}
+
+/// Parses a universal link made by `create_universal_link`.
+///
+/// # Example
+///
+/// ```
+/// use ton_connect::types::{ConnectItem, ConnectRequest};
+/// use ton_connect::helpers::create_universal_link;
+/// use ton_connect::wallet::parse_universal_link;
+///
+/// let connect_request = ConnectRequest {
+///     manifest_url: "https://ton-connect.rs/tonconnect-manifest.json".to_string(),
+///     items: vec![ConnectItem::TonAddressItem],
+/// };
+/// let link = create_universal_link(
+///     "https://app.tonkeeper.com/ton-connect",
+///     "a3baaa66a1eee1dbe79058aca5980a2222fcc418508635f9317e9dc8c3108201",
+///     &connect_request,
+/// )
+/// .unwrap();
+///
+/// let connect_link = parse_universal_link(&link).unwrap();
+/// assert_eq!(connect_link.request.manifest_url, connect_request.manifest_url);
+/// ```
+pub fn parse_universal_link(link: &str) -> Result<ConnectLink, Error> {
+    let url = Url::parse(link)?;
+    let (mut version, mut client_id, mut request, mut ret) = (None, None, None, None);
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "v" => version = Some(value.into_owned()),
+            "id" => client_id = Some(value.into_owned()),
+            "r" => request = Some(value.into_owned()),
+            "ret" => ret = Some(value.into_owned()),
+            _ => {}
+        }
+    }
+
+    let version = version
+        .ok_or(Error::Link("v is missing"))?
+        .parse()
+        .map_err(|_| Error::Link("invalid protocol version"))?;
+    if version != PROTOCOL_VERSION {
+        return Err(Error::Link("unsupported protocol version"));
+    }
+    let client_id = client_id.ok_or(Error::Link("id is missing"))?;
+    if !is_client_id(&client_id) {
+        return Err(Error::Link("invalid client id"));
+    }
+    let request = serde_json::from_str(&request.ok_or(Error::Link("r is missing"))?)?;
+    let ret = ret.map(|ret| ret.parse()).transpose()?;
+    Ok(ConnectLink {
+        version,
+        client_id: client_id.to_lowercase(),
+        request,
+        ret,
+    })
+}
+
+/// Fetches the manifest of the app at `manifest_url` and validates it.
+///
+/// # Example
+///
+/// ```
+/// use ton_connect::wallet::fetch_app_manifest;
+/// use ton_connect::Error;
+///
+/// let fetcher = |_: &str| -> Result<String, Error> {
+///     Ok("{\"url\":\"https://ton-connect.rs\",\"name\":\"TON Connect\",\"iconUrl\":\"https://ton-connect.rs/icon.png\"}".to_string())
+/// };
+/// let manifest = fetch_app_manifest(&fetcher, "https://ton-connect.rs/tonconnect-manifest.json").unwrap();
+/// assert_eq!(manifest.name, "TON Connect");
+/// ```
+pub fn fetch_app_manifest<F: WalletsFetcher>(
+    fetcher: &F,
+    manifest_url: &str,
+) -> Result<AppManifest, Error> {
+    http_url(manifest_url).ok_or(Error::Manifest("invalid manifest url"))?;
+    let manifest = serde_json::from_str(&fetcher.fetch(manifest_url)?)
+        .map_err(|_| Error::Manifest("manifest does not match the expected format"))?;
+    validate_app_manifest(&manifest)?;
+    Ok(manifest)
+}
+
+/// Checks that the manifest has a name and valid http(s) urls.
+pub fn validate_app_manifest(manifest: &AppManifest) -> Result<(), Error> {
+    app_domain(manifest)?;
+    if manifest.name.trim().is_empty() {
+        return Err(Error::Manifest("name is empty"));
+    }
+    http_url(&manifest.icon_url).ok_or(Error::Manifest("invalid icon url"))?;
+    for url in [&manifest.terms_of_use_url, &manifest.privacy_policy_url]
+        .iter()
+        .copied()
+        .flatten()
+    {
+        http_url(url).ok_or(Error::Manifest("invalid document url"))?;
+    }
+    Ok(())
+}
+
+/// Returns the domain of the app, with its port if any, that the `ton_proof` is signed for.
+pub fn app_domain(manifest: &AppManifest) -> Result<String, Error> {
+    let url = http_url(&manifest.url).ok_or(Error::Manifest("invalid app url"))?;
+    let host = url.host_str().ok_or(Error::Manifest("invalid app url"))?;
+    Ok(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+/// Builds the `connect_error` event replying to a connect request that failed with `err`.
+pub fn connect_error_event(id: u32, err: &Error) -> WalletEvent {
+    let code = match err {
+        Error::Link(_) | Error::Json(_) | Error::Url(_) => ConnectErrorCode::BadRequest,
+        Error::Manifest(_) => ConnectErrorCode::ManifestContentError,
+        Error::Io(_) => ConnectErrorCode::ManifestNotFound,
+        #[cfg(feature = "reqwest")]
+        Error::Http(_) => ConnectErrorCode::ManifestNotFound,
+        _ => ConnectErrorCode::UnknownError,
+    };
+    WalletEvent::ConnectError {
+        id,
+        payload: ConnectErrorPayload {
+            code,
+            message: err.to_string(),
+        },
+    }
+}
+
+/// Wallet account shared with the apps in the `ton_addr` item.
+#[derive(Debug, Clone)]
+pub struct WalletAccount {
+    pub address: TonAddress,
+    pub network: NETWORK,
+    /// Base64 (not url safe) encoded stateinit cell for the wallet contract.
+    pub wallet_state_init: String,
+}
+
+/// Wallet answering the connect requests of the apps.
+///
+/// # Example
+///
+/// ```
+/// use ed25519_dalek::SigningKey;
+/// use ton_connect::crypto::ClientKeypair;
+/// use ton_connect::helpers::create_universal_link;
+/// use ton_connect::types::{AppManifest, ConnectItem, ConnectRequest, DeviceInfo, Platform, NETWORK};
+/// use ton_connect::wallet::{parse_universal_link, Wallet, WalletAccount};
+///
+/// let wallet = Wallet::new(
+///     WalletAccount {
+///         address: "0:dc69be3a989b1513f14e9e4ecc46ff6753770a2b199138708ff66238c2719e99".parse().unwrap(),
+///         network: NETWORK::MAINNET,
+///         wallet_state_init: "te6cckEBAQEAAgAAAEysuc0=".to_string(),
+///     },
+///     SigningKey::from_bytes(&[7; 32]),
+///     DeviceInfo {
+///         platform: Platform::Linux,
+///         app_name: "Custodial".to_string(),
+///         app_version: "1.0.0".to_string(),
+///         max_protocol_version: 2,
+///         features: vec![],
+///     },
+/// );
+/// let manifest = AppManifest {
+///     url: "https://ton-connect.rs".to_string(),
+///     name: "TON Connect".to_string(),
+///     icon_url: "https://ton-connect.rs/icon.png".to_string(),
+///     terms_of_use_url: None,
+///     privacy_policy_url: None,
+/// };
+/// let request = ConnectRequest {
+///     manifest_url: "https://ton-connect.rs/tonconnect-manifest.json".to_string(),
+///     items: vec![
+///         ConnectItem::TonAddressItem,
+///         ConnectItem::TonProofItem { payload: "halo".to_string() },
+///     ],
+/// };
+/// let link = create_universal_link(
+///     "https://wallet.example/ton-connect",
+///     "a3baaa66a1eee1dbe79058aca5980a2222fcc418508635f9317e9dc8c3108201",
+///     &request,
+/// )
+/// .unwrap();
+/// let link = parse_universal_link(&link).unwrap();
+///
+/// // To post to the bridge from the session client id to the app client id of the link.
+/// let session = ClientKeypair::generate_random();
+/// let body = wallet.connect_message(1, &link, &session, &manifest).unwrap();
+/// ```
+pub struct Wallet {
+    account: WalletAccount,
+    signing_key: SigningKey,
+    device: DeviceInfo,
+}
+
+impl Wallet {
+    /// Creates a wallet for `account`, signing the proofs with its ed25519 `signing_key`.
+    pub fn new(account: WalletAccount, signing_key: SigningKey, device: DeviceInfo) -> Self {
+        Self {
+            account,
+            signing_key,
+            device,
+        }
+    }
+
+    pub fn account(&self) -> &WalletAccount {
+        &self.account
+    }
+
+    pub fn device(&self) -> &DeviceInfo {
+        &self.device
+    }
+
+    /// Returns the public key of the wallet as a hex string without 0x.
+    pub fn public_key(&self) -> String {
+        hex::encode(self.signing_key.verifying_key().as_bytes())
+    }
+
+    /// Builds the `connect` event replying to `request`, with a `ton_proof` signed for the
+    /// domain of `manifest` if the app asked for one.
+    pub fn connect_event(
+        &self,
+        id: u32,
+        request: &ConnectRequest,
+        manifest: &AppManifest,
+    ) -> Result<WalletEvent, Error> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.connect_event_at(id, request, manifest, now)
+    }
+
+    /// Builds the `connect` event replying to the request of `link`, and seals it from
+    /// `session` for the app client id of the link.
+    ///
+    /// Returns the body to post to the bridge, with `session` as the sender client id.
+    pub fn connect_message(
+        &self,
+        id: u32,
+        link: &ConnectLink,
+        session: &ClientKeypair,
+        manifest: &AppManifest,
+    ) -> Result<String, Error> {
+        let event = self.connect_event(id, &link.request, manifest)?;
+        seal_for(session, &link.client_id, &serde_json::to_vec(&event)?)
+    }
+
+    fn connect_event_at(
+        &self,
+        id: u32,
+        request: &ConnectRequest,
+        manifest: &AppManifest,
+        now: u64,
+    ) -> Result<WalletEvent, Error> {
+        let items = request
+            .items
+            .iter()
+            .map(|item| match item {
+                ConnectItem::TonAddressItem => Ok(ConnectItemReply::TonAddress {
+                    address: self.account.address,
+                    network: self.account.network,
+                    public_key: self.public_key(),
+                    wallet_state_init: self.account.wallet_state_init.clone(),
+                }),
+                ConnectItem::TonProofItem { payload } => {
+                    Ok(ConnectItemReply::TonProofItemReplySuccess {
+                        proof: sign_ton_proof_at(
+                            &self.signing_key,
+                            &self.account.address,
+                            &app_domain(manifest)?,
+                            payload,
+                            now,
+                        ),
+                    })
+                }
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(WalletEvent::Connect {
+            id,
+            payload: ConnectPayload {
+                items,
+                device: self.device.clone(),
+            },
+        })
+    }
+}
+
+impl fmt::Debug for Wallet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Wallet")
+            .field("account", &self.account)
+            .field("public_key", &self.public_key())
+            .field("device", &self.device)
+            .finish()
+    }
+}
+
+/// Parses `url` if it is an http(s) url with a host.
+fn http_url(url: &str) -> Option<Url> {
+    Url::parse(url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https") && url.host_str().is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::open_from;
+    use crate::helpers::create_universal_link;
+    use crate::proof::verify_ton_proof;
+    use crate::types::{BridgeMessage, Feature, Platform};
+    use std::io;
+    use std::time::Duration;
+
+    const APP_ID: &str = "a3baaa66a1eee1dbe79058aca5980a2222fcc418508635f9317e9dc8c3108201";
+    const MANIFEST: &str = "{\"url\":\"https://ton-connect.rs:8443\",\"name\":\"TON Connect\",\"iconUrl\":\"https://ton-connect.rs/icon.png\",\"termsOfUseUrl\":\"https://ton-connect.rs/terms\"}";
+
+    fn connect_request() -> ConnectRequest {
+        ConnectRequest {
+            manifest_url: "https://ton-connect.rs/tonconnect-manifest.json".to_string(),
+            items: vec![
+                ConnectItem::TonAddressItem,
+                ConnectItem::TonProofItem {
+                    payload: "halo".to_string(),
+                },
+            ],
+        }
+    }
+
+    fn wallet() -> Wallet {
+        Wallet::new(
+            WalletAccount {
+                address: "0:dc69be3a989b1513f14e9e4ecc46ff6753770a2b199138708ff66238c2719e99"
+                    .parse()
+                    .unwrap(),
+                network: NETWORK::TESTNET,
+                wallet_state_init: "te6cckEBAQEAAgAAAEysuc0=".to_string(),
+            },
+            SigningKey::from_bytes(&[7u8; 32]),
+            DeviceInfo {
+                platform: Platform::Linux,
+                app_name: "Custodial".to_string(),
+                app_version: "1.0.0".to_string(),
+                max_protocol_version: 2,
+                features: vec![Feature::SendTransaction { max_messages: 4 }],
+            },
+        )
+    }
+
+    #[test]
+    fn test_parse_universal_link() {
+        let link = create_universal_link(
+            "https://app.tonkeeper.com/ton-connect",
+            &APP_ID.to_uppercase(),
+            &connect_request(),
+        )
+        .unwrap();
+        let connect_link = parse_universal_link(&format!("{}&ret=back", link)).unwrap();
+        assert_eq!(connect_link.version, 2);
+        assert_eq!(connect_link.client_id, APP_ID);
+        assert_eq!(connect_link.ret, Some(ReturnStrategy::Back));
+        assert_eq!(connect_link.request.items.len(), 2);
+        assert!(matches!(
+            &connect_link.request.items[1],
+            ConnectItem::TonProofItem { payload } if payload == "halo"
+        ));
+        assert!(parse_universal_link(&link).unwrap().ret.is_none());
+        let ret =
+            |ret: &str| parse_universal_link(&format!("{}&ret={}", link, ret)).map(|link| link.ret);
+        assert_eq!(ret("none").unwrap(), Some(ReturnStrategy::None));
+        assert_eq!(
+            ret("https%3A%2F%2Fton-connect.rs%2Fback").unwrap(),
+            Some(ReturnStrategy::Url(
+                Url::parse("https://ton-connect.rs/back").unwrap()
+            ))
+        );
+        assert!(matches!(
+            ret("javascript%3Aalert(1)").unwrap_err(),
+            Error::Link("invalid ret")
+        ));
+        assert!(matches!(
+            ret("tg%3A%2F%2Fresolve").unwrap_err(),
+            Error::Link("invalid ret")
+        ));
+
+        let invalid = |link: String| parse_universal_link(&link).unwrap_err();
+        assert!(matches!(
+            invalid(link.replace("v=2", "v=1")),
+            Error::Link("unsupported protocol version")
+        ));
+        assert!(matches!(
+            invalid(link.replace("id=", "id=00")),
+            Error::Link("invalid client id")
+        ));
+        assert!(matches!(
+            invalid(link.replace("r=", "request=")),
+            Error::Link("r is missing")
+        ));
+        assert!(matches!(
+            invalid(link.replace("r=%7B", "r=")),
+            Error::Json(_)
+        ));
+        assert!(matches!(invalid("tc://".to_string()), Error::Link(_)));
+    }
+
+    #[test]
+    fn test_fetch_app_manifest() {
+        let fetcher = |url: &str| -> Result<String, Error> {
+            match url {
+                "https://ton-connect.rs/tonconnect-manifest.json" => Ok(MANIFEST.to_string()),
+                "https://ton-connect.rs/broken.json" => {
+                    Ok(MANIFEST.replace("https://ton-connect.rs/icon", "icon"))
+                }
+                _ => Err(io::Error::new(io::ErrorKind::NotFound, "not found").into()),
+            }
+        };
+        let manifest =
+            fetch_app_manifest(&fetcher, "https://ton-connect.rs/tonconnect-manifest.json")
+                .unwrap();
+        assert_eq!(app_domain(&manifest).unwrap(), "ton-connect.rs:8443");
+
+        let err = fetch_app_manifest(&fetcher, "https://ton-connect.rs/broken.json").unwrap_err();
+        assert!(matches!(err, Error::Manifest("invalid icon url")));
+        let err = fetch_app_manifest(&fetcher, "https://ton-connect.rs/missing.json").unwrap_err();
+        assert!(matches!(err, Error::Io(_)));
+        let err = fetch_app_manifest(&fetcher, "file:///etc/passwd").unwrap_err();
+        assert!(matches!(err, Error::Manifest("invalid manifest url")));
+
+        let mut manifest = manifest;
+        manifest.name = " ".to_string();
+        assert!(validate_app_manifest(&manifest).is_err());
+    }
+
+    #[test]
+    fn test_connect_event() {
+        let wallet = wallet();
+        let manifest: AppManifest = serde_json::from_str(MANIFEST).unwrap();
+        let app = ClientKeypair::generate_random();
+        let link = create_universal_link(
+            "https://app.tonkeeper.com/ton-connect",
+            &app.get_hex_public(),
+            &connect_request(),
+        )
+        .unwrap();
+        let link = parse_universal_link(&link).unwrap();
+
+        // Through the bridge, encrypted with the wallet session keypair.
+        let session = ClientKeypair::generate_random();
+        let message = wallet
+            .connect_message(1, &link, &session, &manifest)
+            .unwrap();
+        let bridge_message = BridgeMessage {
+            from: session.get_hex_public(),
+            message,
+        };
+        let event: WalletEvent =
+            serde_json::from_slice(&open_from(&app, &bridge_message).unwrap()).unwrap();
+
+        let items = match event {
+            WalletEvent::Connect { id: 1, payload } => payload.items,
+            other => panic!("unexpected event: {:?}", other),
+        };
+        let address = match &items[0] {
+            ConnectItemReply::TonAddress {
+                address,
+                network,
+                public_key,
+                ..
+            } => {
+                assert_eq!(*network, NETWORK::TESTNET);
+                assert_eq!(public_key, &wallet.public_key());
+                *address
+            }
+            other => panic!("unexpected item: {:?}", other),
+        };
+        match &items[1] {
+            ConnectItemReply::TonProofItemReplySuccess { proof } => {
+                assert_eq!(proof.domain.length_bytes, 19);
+                verify_ton_proof(
+                    &address,
+                    &wallet.public_key(),
+                    proof,
+                    "ton-connect.rs:8443",
+                    Duration::from_secs(60),
+                )
+                .unwrap();
+            }
+            other => panic!("unexpected item: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_connect_error_event() {
+        let code = |err: Error| match connect_error_event(2, &err) {
+            WalletEvent::ConnectError { id: 2, payload } => payload.code,
+            other => panic!("unexpected event: {:?}", other),
+        };
+        assert_eq!(
+            code(Error::Link("r is missing")),
+            ConnectErrorCode::BadRequest
+        );
+        assert_eq!(
+            code(Error::Manifest("name is empty")),
+            ConnectErrorCode::ManifestContentError
+        );
+        assert_eq!(
+            code(io::Error::new(io::ErrorKind::NotFound, "not found").into()),
+            ConnectErrorCode::ManifestNotFound
+        );
+        assert_eq!(code(Error::Decryption), ConnectErrorCode::UnknownError);
+    }
+}
@@ -0,0 +1,222 @@
+//! List of the wallets supporting TON Connect, to show a wallet picker.
+//!
+//! The list is published as `wallets-v2.json`. A snapshot is bundled with the crate and used
+//! when the published list cannot be fetched.
+
+#[cfg(feature = "blocking")]
+use crate::connector::WalletConnectionSource;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+
+/// Published list of the wallets supporting TON Connect.
+pub const WALLETS_LIST_URL: &str =
+    "https://raw.githubusercontent.com/ton-blockchain/wallets-list/main/wallets-v2.json";
+
+/// Snapshot of the wallets list bundled with the crate, possibly outdated.
+const BUNDLED_WALLETS_LIST: &str = include_str!("wallets-v2.json");
+
+/// A wallet of the wallets list.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WalletInfo {
+    /// Wallet identifier, e.g. `tonkeeper`.
+    pub app_name: String,
+    /// Human readable name of the wallet.
+    pub name: String,
+    /// Url to the wallet icon.
+    pub image: String,
+    /// Url to the wallet website.
+    pub about_url: String,
+    /// Base of the universal link opening the wallet, for wallets with an HTTP bridge.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub universal_url: Option<String>,
+    /// Custom scheme deep link opening the wallet, preferred to the universal link on the same device.
+    #[serde(rename = "deepLink", skip_serializing_if = "Option::is_none")]
+    pub deep_link: Option<String>,
+    /// TON DNS name of the wallet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tondns: Option<String>,
+    /// Ways to reach the wallet.
+    pub bridge: Vec<WalletBridge>,
+    /// Platforms the wallet runs on, e.g. `ios`, `android`, `chrome`, `macos`.
+    pub platforms: Vec<String>,
+}
+
+/// A way to reach a wallet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum WalletBridge {
+    /// HTTP bridge with server-sent events, as used by `bridge::BlockingBridgeClient`.
+    #[serde(rename = "sse")]
+    Sse { url: String },
+    /// Browser extension injected as `window[key].tonconnect`.
+    #[serde(rename = "js")]
+    Js { key: String },
+}
+
+impl WalletInfo {
+    /// Url of the HTTP bridge of the wallet, if it has one.
+    pub fn sse_bridge_url(&self) -> Option<&str> {
+        self.bridge.iter().find_map(|bridge| match bridge {
+            WalletBridge::Sse { url } => Some(url.as_str()),
+            WalletBridge::Js { .. } => None,
+        })
+    }
+
+    /// Key of the browser extension of the wallet, if it has one.
+    pub fn js_bridge_key(&self) -> Option<&str> {
+        self.bridge.iter().find_map(|bridge| match bridge {
+            WalletBridge::Js { key } => Some(key.as_str()),
+            WalletBridge::Sse { .. } => None,
+        })
+    }
+
+    /// Where to reach the wallet with `connector::TonConnect`, if it has an HTTP bridge.
+    #[cfg(feature = "blocking")]
+    pub fn connection_source(&self) -> Option<WalletConnectionSource> {
+        Some(WalletConnectionSource {
+            universal_url: self.universal_url.clone()?,
+            bridge_url: self.sse_bridge_url()?.to_string(),
+        })
+    }
+}
+
+/// Fetches the body of the wallets list from an url.
+///
+/// Implemented for closures, so the list can be loaded from anywhere, and for the blocking
+/// `reqwest` client with the `blocking` feature.
+pub trait WalletsFetcher {
+    fn fetch(&self, url: &str) -> Result<String, Error>;
+}
+
+impl<F> WalletsFetcher for F
+where
+    F: Fn(&str) -> Result<String, Error>,
+{
+    fn fetch(&self, url: &str) -> Result<String, Error> {
+        self(url)
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl WalletsFetcher for reqwest::blocking::Client {
+    fn fetch(&self, url: &str) -> Result<String, Error> {
+        Ok(self.get(url).send()?.error_for_status()?.text()?)
+    }
+}
+
+/// Parses a wallets list, skipping the wallets that do not match the expected format.
+///
+/// # Example
+///
+/// ```
+/// use ton_connect::wallets::parse_wallets_list;
+///
+/// let wallets = parse_wallets_list("[{\"app_name\":\"tonkeeper\",\"name\":\"Tonkeeper\",\"image\":\"https://tonkeeper.com/assets/tonconnect-icon.png\",\"about_url\":\"https://tonkeeper.com\",\"universal_url\":\"https://app.tonkeeper.com/ton-connect\",\"bridge\":[{\"type\":\"sse\",\"url\":\"https://bridge.tonapi.io/bridge\"}],\"platforms\":[\"ios\"]}]").unwrap();
+/// assert_eq!(wallets[0].sse_bridge_url(), Some("https://bridge.tonapi.io/bridge"));
+/// ```
+pub fn parse_wallets_list(json: &str) -> Result<Vec<WalletInfo>, Error> {
+    let entries: Vec<serde_json::Value> = serde_json::from_str(json)?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| serde_json::from_value(entry).ok())
+        .collect())
+}
+
+/// Returns the wallets list bundled with the crate.
+pub fn bundled_wallets_list() -> Vec<WalletInfo> {
+    parse_wallets_list(BUNDLED_WALLETS_LIST).expect("valid bundled wallets list")
+}
+
+/// Fetches and parses the wallets list at `url`.
+pub fn fetch_wallets_list<F: WalletsFetcher>(
+    fetcher: &F,
+    url: &str,
+) -> Result<Vec<WalletInfo>, Error> {
+    parse_wallets_list(&fetcher.fetch(url)?)
+}
+
+/// Loads the published wallets list, falling back to the bundled one if it cannot be fetched or parsed.
+///
+/// # Example
+///
+/// ```
+/// use std::io;
+/// use ton_connect::wallets::load_wallets_list;
+/// use ton_connect::Error;
+///
+/// // Offline: the fetcher fails, so the bundled list is returned.
+/// let wallets = load_wallets_list(&|_: &str| -> Result<String, Error> {
+///     Err(io::Error::new(io::ErrorKind::NotConnected, "offline").into())
+/// });
+/// assert!(wallets.iter().any(|wallet| wallet.app_name == "tonkeeper"));
+/// ```
+pub fn load_wallets_list<F: WalletsFetcher>(fetcher: &F) -> Vec<WalletInfo> {
+    match fetch_wallets_list(fetcher, WALLETS_LIST_URL) {
+        Ok(wallets) if !wallets.is_empty() => wallets,
+        _ => bundled_wallets_list(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WALLETS: &str = "[
+        {\"app_name\":\"tonkeeper\",\"name\":\"Tonkeeper\",\"image\":\"https://tonkeeper.com/assets/tonconnect-icon.png\",\"tondns\":\"tonkeeper.ton\",\"about_url\":\"https://tonkeeper.com\",\"universal_url\":\"https://app.tonkeeper.com/ton-connect\",\"deepLink\":\"tonkeeper-tc://\",\"bridge\":[{\"type\":\"sse\",\"url\":\"https://bridge.tonapi.io/bridge\"},{\"type\":\"js\",\"key\":\"tonkeeper\"}],\"platforms\":[\"ios\",\"android\",\"chrome\"]},
+        {\"app_name\":\"broken\",\"name\":\"Broken\"},
+        {\"app_name\":\"extension\",\"name\":\"Extension\",\"image\":\"https://example.com/icon.png\",\"about_url\":\"https://example.com\",\"bridge\":[{\"type\":\"js\",\"key\":\"extension\"}],\"platforms\":[\"chrome\"]}
+    ]";
+
+    #[test]
+    fn test_parse_wallets_list() {
+        let wallets = parse_wallets_list(WALLETS).unwrap();
+        assert_eq!(wallets.len(), 2);
+
+        let tonkeeper = &wallets[0];
+        assert_eq!(tonkeeper.deep_link.as_deref(), Some("tonkeeper-tc://"));
+        assert_eq!(tonkeeper.tondns.as_deref(), Some("tonkeeper.ton"));
+        assert_eq!(
+            tonkeeper.sse_bridge_url(),
+            Some("https://bridge.tonapi.io/bridge")
+        );
+        assert_eq!(tonkeeper.js_bridge_key(), Some("tonkeeper"));
+
+        let extension = &wallets[1];
+        assert_eq!(extension.universal_url, None);
+        assert_eq!(extension.sse_bridge_url(), None);
+
+        assert!(parse_wallets_list("{}").is_err());
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn test_connection_source() {
+        let wallets = parse_wallets_list(WALLETS).unwrap();
+        let source = wallets[0].connection_source().unwrap();
+        assert_eq!(
+            source.universal_url,
+            "https://app.tonkeeper.com/ton-connect"
+        );
+        assert_eq!(source.bridge_url, "https://bridge.tonapi.io/bridge");
+        assert!(wallets[1].connection_source().is_none());
+    }
+
+    #[test]
+    fn test_load_wallets_list() {
+        let fetched = load_wallets_list(&|url: &str| -> Result<String, Error> {
+            assert_eq!(url, WALLETS_LIST_URL);
+            Ok(WALLETS.to_string())
+        });
+        assert_eq!(fetched.len(), 2);
+
+        let bundled = bundled_wallets_list();
+        assert!(bundled.iter().all(|wallet| !wallet.bridge.is_empty()));
+        let offline = load_wallets_list(&|_: &str| -> Result<String, Error> {
+            Err(std::io::Error::new(std::io::ErrorKind::NotConnected, "offline").into())
+        });
+        assert_eq!(offline, bundled);
+        let invalid =
+            load_wallets_list(&|_: &str| -> Result<String, Error> { Ok("<html>".to_string()) });
+        assert_eq!(invalid, bundled);
+    }
+}